@@ -0,0 +1,322 @@
+use std::convert::TryFrom;
+
+pub type Ip = i32;
+pub type Acc = i32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Registers {
+    pub acc: Acc,
+}
+
+/// Where execution continues after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flow {
+    Next,
+    Jump(Ip),
+    Halt,
+}
+
+pub trait Instruction<R = Registers> {
+    fn execute(&self, registers: &mut R) -> Flow;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Acc(Acc),
+    Jmp(Ip),
+    Nop(Ip),
+}
+
+impl Op {
+    pub fn invert(&self) -> Op {
+        use Op::*;
+        match *self {
+            Jmp(v) => Nop(v),
+            Nop(v) => Jmp(v),
+            op => op,
+        }
+    }
+}
+
+impl Instruction for Op {
+    fn execute(&self, registers: &mut Registers) -> Flow {
+        match *self {
+            Op::Acc(v) => {
+                registers.acc += v;
+                Flow::Next
+            }
+            Op::Jmp(offset) => Flow::Jump(offset),
+            Op::Nop(_) => Flow::Next,
+        }
+    }
+}
+
+impl TryFrom<&str> for Op {
+    type Error = ();
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let value = s.get(4..).ok_or(())?.parse().map_err(|_| ())?;
+
+        Ok(match s.get(..3).ok_or(())? {
+            "acc" => Op::Acc(value),
+            "jmp" => Op::Jmp(value),
+            "nop" => Op::Nop(value),
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Termination {
+    /// The instruction right after the last one was reached, or a [`Flow::Halt`] was executed.
+    Halted,
+    /// The instruction at `ip` was about to be executed a second time, only reported
+    /// with [`Machine::with_loop_detection`].
+    InfiniteLoop { ip: Ip },
+    /// A jump targeted `ip`, outside of the program.
+    OutOfBounds { ip: Ip },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry<R> {
+    pub ip: Ip,
+    /// register state after executing the instruction at `ip`
+    pub registers: R,
+}
+
+#[derive(Debug, Clone)]
+pub struct Machine<'p, I, R = Registers> {
+    program: &'p [I],
+    pub registers: R,
+    ip: Ip,
+    visited: Option<Vec<bool>>,
+    trace: Option<Vec<TraceEntry<R>>>,
+    terminated: Option<Termination>,
+}
+
+impl<'p, I: Instruction<R>, R: Clone + Default> Machine<'p, I, R> {
+    pub fn new(program: &'p [I]) -> Self {
+        Self::with_registers(program, R::default())
+    }
+}
+
+impl<'p, I: Instruction<R>, R: Clone> Machine<'p, I, R> {
+    pub fn with_registers(program: &'p [I], registers: R) -> Self {
+        Machine {
+            program,
+            registers,
+            ip: 0,
+            visited: None,
+            trace: None,
+            terminated: program.is_empty().then_some(Termination::Halted),
+        }
+    }
+
+    /// Stops with [`Termination::InfiniteLoop`] before any instruction runs twice.
+    ///
+    /// This is only a loop for instructions whose control flow doesn't depend on the
+    /// registers, like [`Op`].
+    pub fn with_loop_detection(mut self) -> Self {
+        self.visited = Some(vec![false; self.program.len()]);
+        self
+    }
+
+    /// Records every executed instruction, see [`Machine::trace`].
+    pub fn traced(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    pub fn ip(&self) -> Ip {
+        self.ip
+    }
+
+    pub fn program(&self) -> &'p [I] {
+        self.program
+    }
+
+    pub fn trace(&self) -> Option<&[TraceEntry<R>]> {
+        self.trace.as_deref()
+    }
+
+    pub fn termination(&self) -> Option<Termination> {
+        self.terminated
+    }
+
+    /// Instruction to be executed by the next [`Machine::step`], if still running.
+    pub fn next_instruction(&self) -> Option<&'p I> {
+        match self.terminated {
+            None => self.program.get(self.ip as usize),
+            Some(_) => None,
+        }
+    }
+
+    /// Executes a single instruction, returning why the machine stopped if it did.
+    pub fn step(&mut self) -> Option<Termination> {
+        if self.terminated.is_some() {
+            return self.terminated;
+        }
+
+        let index = self.ip as usize;
+        if let Some(visited) = &mut self.visited {
+            if std::mem::replace(&mut visited[index], true) {
+                return self.terminate(Termination::InfiniteLoop { ip: self.ip });
+            }
+        }
+
+        let flow = self.program[index].execute(&mut self.registers);
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                ip: self.ip,
+                registers: self.registers.clone(),
+            });
+        }
+
+        match flow {
+            Flow::Next => self.ip += 1,
+            Flow::Jump(offset) => self.ip += offset,
+            Flow::Halt => return self.terminate(Termination::Halted),
+        }
+
+        match self.ip {
+            ip if ip == self.program.len() as Ip => self.terminate(Termination::Halted),
+            ip if ip < 0 || ip > self.program.len() as Ip => {
+                self.terminate(Termination::OutOfBounds { ip })
+            }
+            _ => None,
+        }
+    }
+
+    fn terminate(&mut self, termination: Termination) -> Option<Termination> {
+        self.terminated = Some(termination);
+        self.terminated
+    }
+
+    /// Steps until `stop` returns `true` before an instruction is executed, or the machine
+    /// terminates.
+    pub fn run_until(&mut self, mut stop: impl FnMut(&Self) -> bool) -> Option<Termination> {
+        loop {
+            if self.terminated.is_some() {
+                return self.terminated;
+            }
+            if stop(self) {
+                return None;
+            }
+            self.step();
+        }
+    }
+
+    pub fn run(&mut self) -> Termination {
+        self.run_until(|_| false).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_PROGRAM: &[Op] = &[
+        Op::Nop(0),
+        Op::Acc(1),
+        Op::Jmp(4),
+        Op::Acc(3),
+        Op::Jmp(-3),
+        Op::Acc(-99),
+        Op::Acc(1),
+        Op::Jmp(-4),
+        Op::Acc(6),
+    ];
+
+    #[test]
+    fn parse() {
+        assert_eq!(Op::try_from("acc -7"), Ok(Op::Acc(-7)));
+        assert_eq!(Op::try_from("jmp +2"), Ok(Op::Jmp(2)));
+        assert_eq!(Op::try_from("aaé+1"), Err(()));
+        assert_eq!(Op::try_from("éé +1"), Err(()));
+        assert_eq!(Op::try_from("xyz +1"), Err(()));
+    }
+
+    #[test]
+    fn termination() {
+        let mut machine = Machine::new(EXAMPLE_PROGRAM).with_loop_detection();
+        assert_eq!(machine.run(), Termination::InfiniteLoop { ip: 1 });
+        assert_eq!(machine.registers.acc, 5);
+
+        let mut machine = Machine::new(&EXAMPLE_PROGRAM[..2]);
+        assert_eq!(machine.run(), Termination::Halted);
+        assert_eq!(machine.registers.acc, 1);
+
+        let mut machine = Machine::new(&EXAMPLE_PROGRAM[..4]);
+        assert_eq!(machine.run(), Termination::OutOfBounds { ip: 6 });
+
+        let mut machine = Machine::new(&[Op::Jmp(-1)]);
+        assert_eq!(machine.run(), Termination::OutOfBounds { ip: -1 });
+    }
+
+    #[test]
+    fn run_until_and_trace() {
+        let mut machine = Machine::new(EXAMPLE_PROGRAM).with_loop_detection().traced();
+        assert_eq!(machine.run_until(|m| m.registers.acc >= 4), None);
+        assert_eq!(machine.ip(), 4);
+        assert_eq!(machine.next_instruction(), Some(&Op::Jmp(-3)));
+
+        assert_eq!(machine.run(), Termination::InfiniteLoop { ip: 1 });
+        let ips: Vec<_> = machine.trace().unwrap().iter().map(|e| e.ip).collect();
+        assert_eq!(ips, [0, 1, 2, 6, 7, 3, 4]);
+        assert_eq!(machine.trace().unwrap()[4].registers.acc, 2);
+    }
+
+    #[derive(Debug, Default, Clone)]
+    struct Extended {
+        acc: i64,
+        out: Vec<i64>,
+    }
+
+    enum ExtendedOp {
+        Add(i64),
+        Mul(i64),
+        Out,
+        Hlt,
+        /// jumps by the offset unless the accumulator is zero
+        Jnz(Ip),
+    }
+
+    impl Instruction<Extended> for ExtendedOp {
+        fn execute(&self, registers: &mut Extended) -> Flow {
+            match *self {
+                ExtendedOp::Add(v) => registers.acc += v,
+                ExtendedOp::Mul(v) => registers.acc *= v,
+                ExtendedOp::Out => registers.out.push(registers.acc),
+                ExtendedOp::Hlt => return Flow::Halt,
+                ExtendedOp::Jnz(offset) if registers.acc != 0 => return Flow::Jump(offset),
+                ExtendedOp::Jnz(_) => {}
+            }
+            Flow::Next
+        }
+    }
+
+    #[test]
+    fn extended_instruction_set() {
+        use ExtendedOp::*;
+
+        let program = [Add(3), Mul(7), Out, Hlt, Out];
+        let mut machine = Machine::<_, Extended>::new(&program);
+        assert_eq!(machine.run(), Termination::Halted);
+        assert_eq!(machine.registers.out, [21]);
+    }
+
+    #[test]
+    fn revisits_without_loop_detection() {
+        use ExtendedOp::*;
+
+        // counts down from 3, running the loop body three times
+        let program = [Add(3), Out, Add(-1), Jnz(-2), Out];
+        let mut machine = Machine::<_, Extended>::new(&program);
+        assert_eq!(machine.run(), Termination::Halted);
+        assert_eq!(machine.registers.out, [3, 2, 1, 0]);
+
+        let mut machine = Machine::<_, Extended>::new(&program).with_loop_detection();
+        assert_eq!(machine.run(), Termination::InfiniteLoop { ip: 1 });
+        assert_eq!(machine.registers.out, [3]);
+    }
+}
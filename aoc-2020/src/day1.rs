@@ -1,60 +1,126 @@
 use super::{aoc, aoc_generator};
 use aoc_utils::parse_lines;
-use std::collections::HashSet;
 
 #[aoc_generator(day1)]
 pub fn generate(s: &str) -> Vec<i32> {
     parse_lines(s).expect("couldn't parse input")
 }
 
-#[aoc(day1, part1, online)]
-pub fn day1_part1_online(values: &[i32]) -> i32 {
-    let mut seen = HashSet::new();
+/// Calls `found` with every set of `k` distinct indices (in increasing order) whose values sum
+/// to `target`, until it returns `false`.
+fn k_sum_with(values: &[i32], k: usize, target: i32, mut found: impl FnMut(&[usize]) -> bool) {
+    let mut sorted: Vec<usize> = (0..values.len()).collect();
+    sorted.sort_by_key(|&i| values[i]);
+    let sorted: Vec<(i64, usize)> = sorted.into_iter().map(|i| (values[i] as i64, i)).collect();
 
-    for v in values.iter() {
-        let inverted = 2020 - v;
-        if seen.contains(&inverted) {
-            return v * inverted;
-        } else {
-            seen.insert(v);
+    let mut chosen = Vec::with_capacity(k);
+    let mut report = |chosen: &[usize]| {
+        let mut indices = chosen.to_owned();
+        indices.sort_unstable();
+        found(&indices)
+    };
+
+    search(&sorted, k, target as i64, &mut chosen, &mut report);
+
+    /// Returns `false` if the search should stop.
+    fn search(
+        sorted: &[(i64, usize)],
+        k: usize,
+        target: i64,
+        chosen: &mut Vec<usize>,
+        report: &mut impl FnMut(&[usize]) -> bool,
+    ) -> bool {
+        match k {
+            0 => return target != 0 || report(chosen),
+            1 => {
+                let start = sorted.partition_point(|&(v, _)| v < target);
+                for &(_, i) in sorted[start..].iter().take_while(|&&(v, _)| v == target) {
+                    chosen.push(i);
+                    let go_on = report(chosen);
+                    chosen.pop();
+                    if !go_on {
+                        return false;
+                    }
+                }
+            }
+            2 => {
+                let (mut lo, mut hi) = (0, sorted.len());
+                while lo + 1 < hi {
+                    let sum = sorted[lo].0 + sorted[hi - 1].0;
+                    if sum < target {
+                        lo += 1;
+                    } else if sum > target {
+                        hi -= 1;
+                    } else {
+                        // every pairing of the runs of equal values at both ends
+                        let lo_end =
+                            lo + sorted[lo..hi].partition_point(|&(v, _)| v == sorted[lo].0);
+                        let hi_start =
+                            lo + sorted[lo..hi].partition_point(|&(v, _)| v < sorted[hi - 1].0);
+                        for a in lo..lo_end {
+                            for b in hi_start.max(a + 1)..hi {
+                                chosen.extend([sorted[a].1, sorted[b].1]);
+                                let go_on = report(chosen);
+                                chosen.truncate(chosen.len() - 2);
+                                if !go_on {
+                                    return false;
+                                }
+                            }
+                        }
+                        lo = lo_end;
+                        hi = hi_start;
+                    }
+                }
+            }
+            _ => {
+                for first in 0..sorted.len() {
+                    let (v, i) = sorted[first];
+                    chosen.push(i);
+                    let go_on = search(&sorted[first + 1..], k - 1, target - v, chosen, report);
+                    chosen.pop();
+                    if !go_on {
+                        return false;
+                    }
+                }
+            }
         }
+        true
     }
+}
 
-    panic!("couldn't find value")
+/// All sets of `k` distinct indices whose values sum to `target`, each in increasing order.
+pub fn k_sum_all(values: &[i32], k: usize, target: i32) -> Vec<Vec<usize>> {
+    let mut all = Vec::new();
+    k_sum_with(values, k, target, |indices| {
+        all.push(indices.to_owned());
+        true
+    });
+    all.sort_unstable();
+    all
 }
 
-#[aoc(day1, part1, premade)]
-pub fn day1_part1_premade(values: &[i32]) -> i32 {
-    let seen: HashSet<_> = values.iter().copied().collect();
+/// Any set of `k` distinct indices whose values sum to `target`.
+pub fn k_sum(values: &[i32], k: usize, target: i32) -> Option<Vec<usize>> {
+    let mut first = None;
+    k_sum_with(values, k, target, |indices| {
+        first = Some(indices.to_owned());
+        false
+    });
+    first
+}
 
-    for v in values.iter() {
-        let inverted = 2020 - v;
-        if seen.contains(&inverted) {
-            return v * inverted;
-        }
-    }
+fn k_sum_product(values: &[i32], k: usize, target: i32) -> Option<i32> {
+    k_sum(values, k, target).map(|indices| indices.iter().map(|&i| values[i]).product())
+}
 
-    panic!("couldn't find value")
+#[aoc(day1, part1)]
+pub fn day1_part1(values: &[i32]) -> i32 {
+    k_sum_product(values, 2, 2020).expect("couldn't find value")
 }
 
 #[aoc(day1, part2)]
 pub fn day1_part2(values: &[i32]) -> i32 {
-    let seen: HashSet<_> = values.iter().copied().collect();
-
-    for v1 in values.iter() {
-        for v2 in values.iter() {
-            if v1 == v2 {
-                continue;
-            }
-
-            let inverted = 2020 - v1 - v2;
-            if seen.contains(&inverted) {
-                return v1 * v2 * inverted;
-            }
-        }
-    }
-
-    panic!("couldn't find the value")
+    k_sum_product(values, 3, 2020).expect("couldn't find the value")
 }
 
 #[cfg(test)]
@@ -62,17 +128,62 @@ mod tests {
     const EXAMPLE_DATA: &[i32] = &[1721, 979, 366, 299, 675, 1456];
 
     #[test]
-    fn day1_part1_premade() {
-        assert_eq!(super::day1_part1_premade(EXAMPLE_DATA), 514579);
+    fn day1_part2() {
+        assert_eq!(super::day1_part2(EXAMPLE_DATA), 241861950);
     }
 
     #[test]
-    fn day1_part1_online() {
-        assert_eq!(super::day1_part1_online(EXAMPLE_DATA), 514579);
+    fn day1_part1() {
+        assert_eq!(super::day1_part1(EXAMPLE_DATA), 514579);
+        // 1010 can't be paired with itself
+        assert_eq!(super::day1_part1(&[1010, 1721, 299]), 514579);
+        assert_eq!(super::day1_part1(&[1010, 3, 1010]), 1020100);
     }
 
     #[test]
-    fn day1_part2() {
-        assert_eq!(super::day1_part2(EXAMPLE_DATA), 241861950);
+    fn k_sum_all() {
+        assert_eq!(super::k_sum_all(EXAMPLE_DATA, 2, 2020), [[0, 3]]);
+        assert_eq!(super::k_sum_all(EXAMPLE_DATA, 3, 2020), [[1, 2, 4]]);
+        assert_eq!(super::k_sum_all(EXAMPLE_DATA, 1, 366), [[2]]);
+        assert_eq!(super::k_sum_all(EXAMPLE_DATA, 0, 0), [[0_usize; 0]]);
+        assert!(super::k_sum_all(EXAMPLE_DATA, 4, 2020).is_empty());
+        assert!(super::k_sum_all(EXAMPLE_DATA, 7, 0).is_empty());
+    }
+
+    #[test]
+    fn duplicates() {
+        let values = [1010, 5, 1010, 1010, 1005, 1010];
+        assert_eq!(
+            super::k_sum_all(&values, 2, 2020),
+            [[0, 2], [0, 3], [0, 5], [2, 3], [2, 5], [3, 5]]
+        );
+        assert_eq!(
+            super::k_sum_all(&values, 3, 2020),
+            [[0, 1, 4], [1, 2, 4], [1, 3, 4], [1, 4, 5]]
+        );
+        assert_eq!(super::k_sum(&[1010, 3], 2, 2020), None);
+
+        let values = [673, 673, 674];
+        assert_eq!(super::k_sum(&values, 3, 2020), Some(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        use aoc_utils::libs::itertools::Itertools;
+
+        let values = [3, -2, 7, 3, 0, 5, 3, -2, 8, 1, 0, 4];
+        for k in 0..5 {
+            for target in -5..20 {
+                let expected = (0..values.len())
+                    .combinations(k)
+                    .filter(|c| c.iter().map(|&i| values[i]).sum::<i32>() == target)
+                    .collect::<Vec<_>>();
+                assert_eq!(super::k_sum_all(&values, k, target), expected);
+                assert_eq!(
+                    super::k_sum(&values, k, target).is_some(),
+                    !expected.is_empty()
+                );
+            }
+        }
     }
 }
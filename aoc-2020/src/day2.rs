@@ -1,55 +1,113 @@
-#![allow(clippy::naive_bytecount)]
-
 use super::{aoc, aoc_generator};
 use aoc_utils::try_from_lines;
 use std::convert::{TryFrom, TryInto};
 
 #[derive(Debug, PartialEq)]
 pub struct Line {
-    value1: u8,
-    value2: u8,
-    letter: u8,
-    password: Vec<u8>,
+    value1: usize,
+    value2: usize,
+    letter: String,
+    password: String,
 }
 
 impl TryFrom<&str> for Line {
     type Error = ();
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        let mut s = s.as_bytes().iter();
-        let value1 = parse_bound(&mut s, b'-')?;
-        let value2 = parse_bound(&mut s, b' ')?;
-        let letter = *s.next().ok_or(())?;
-        s.next().ok_or(())?;
-        s.next().ok_or(())?; // b": "
-        let password = s.as_slice().to_owned();
-
-        fn parse_bound<'e>(
-            iter: &mut impl Iterator<Item = &'e u8>,
-            stop_char: u8,
-        ) -> Result<u8, ()> {
-            let mut value = 0;
-            for b in iter {
-                match b {
-                    v @ b'0'..=b'9' => {
-                        value = value * 10 + (v - b'0');
-                    }
-                    &c if c == stop_char => break,
-                    _ => return Err(()),
-                }
+        let (bounds, rest) = s.split_once(' ').ok_or(())?;
+        let (value1, value2) = bounds.split_once('-').ok_or(())?;
+        let (letter, password) = rest.split_once(": ").ok_or(())?;
+        if letter.is_empty() {
+            return Err(());
+        }
+
+        fn parse_bound(s: &str) -> Result<usize, ()> {
+            if !s.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(());
             }
-            Ok(value)
+            s.parse().map_err(|_| ())
         }
 
         Ok(Line {
-            value1,
-            value2,
-            letter,
-            password,
+            value1: parse_bound(value1)?,
+            value2: parse_bound(value2)?,
+            letter: letter.to_owned(),
+            password: password.to_owned(),
         })
     }
 }
 
+impl Line {
+    /// Whether the password contains the letter starting at the given (1-based) character
+    /// position.
+    fn letter_at(&self, position: usize) -> bool {
+        position
+            .checked_sub(1)
+            .and_then(|index| self.password.char_indices().nth(index))
+            .is_some_and(|(offset, _)| self.password[offset..].starts_with(&self.letter))
+    }
+
+    /// Number of, possibly overlapping, occurrences of the letter in the password.
+    fn letter_count(&self) -> usize {
+        self.password
+            .char_indices()
+            .filter(|&(offset, _)| self.password[offset..].starts_with(&self.letter))
+            .count()
+    }
+}
+
+pub trait PasswordPolicy {
+    fn name(&self) -> &'static str;
+    fn is_valid(&self, line: &Line) -> bool;
+}
+
+/// The letter must occur between `value1` and `value2` times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OccurrenceRange;
+
+impl PasswordPolicy for OccurrenceRange {
+    fn name(&self) -> &'static str {
+        "occurrence range"
+    }
+
+    fn is_valid(&self, line: &Line) -> bool {
+        (line.value1..=line.value2).contains(&line.letter_count())
+    }
+}
+
+/// The letter must occur at exactly one of the positions `value1` and `value2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PositionXor;
+
+impl PasswordPolicy for PositionXor {
+    fn name(&self) -> &'static str {
+        "position xor"
+    }
+
+    fn is_valid(&self, line: &Line) -> bool {
+        line.letter_at(line.value1) ^ line.letter_at(line.value2)
+    }
+}
+
+/// Index of each line violating a policy, together with the names of the violated policies.
+pub fn violations(
+    lines: &[Line],
+    policies: &[&dyn PasswordPolicy],
+) -> Vec<(usize, Vec<&'static str>)> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let violated: Vec<_> = policies
+                .iter()
+                .filter(|policy| !policy.is_valid(line))
+                .map(|policy| policy.name())
+                .collect();
+            (!violated.is_empty()).then_some((i, violated))
+        })
+        .collect()
+}
+
 #[aoc_generator(day2)]
 pub fn generate(s: &str) -> Vec<Line> {
     try_from_lines(s).expect("couldn't parse input")
@@ -59,10 +117,7 @@ pub fn generate(s: &str) -> Vec<Line> {
 pub fn day2_part1_filter_count(values: &[Line]) -> i32 {
     values
         .iter()
-        .filter(|line| {
-            let count = line.password.iter().filter(|&&c| c == line.letter).count();
-            (line.value1..=line.value2).contains(&count.try_into().expect("letter count too large"))
-        })
+        .filter(|line| OccurrenceRange.is_valid(line))
         .count()
         .try_into()
         .expect("valid password count too large")
@@ -72,14 +127,7 @@ pub fn day2_part1_filter_count(values: &[Line]) -> i32 {
 pub fn day2_part1_map_sum(values: &[Line]) -> i32 {
     values
         .iter()
-        .map(|line| {
-            let count = line
-                .password
-                .iter()
-                .map(|&c| (c == line.letter) as u8)
-                .sum();
-            (line.value1..=line.value2).contains(&count) as i32
-        })
+        .map(|line| OccurrenceRange.is_valid(line) as i32)
         .sum()
 }
 
@@ -87,10 +135,7 @@ pub fn day2_part1_map_sum(values: &[Line]) -> i32 {
 pub fn day2_part2(values: &[Line]) -> i32 {
     values
         .iter()
-        .filter(|line| {
-            (line.password[(line.value1 - 1) as usize] == line.letter)
-                ^ (line.password[(line.value2 - 1) as usize] == line.letter)
-        })
+        .filter(|line| PositionXor.is_valid(line))
         .count()
         .try_into()
         .expect("valid password count too large")
@@ -98,7 +143,9 @@ pub fn day2_part2(values: &[Line]) -> i32 {
 
 #[cfg(test)]
 mod tests {
-    use super::Line;
+    use std::convert::TryFrom;
+
+    use super::{Line, OccurrenceRange, PositionXor};
 
     const EXAMPLE_INPUT: &str = r"1-3 a: abcde
 1-3 b: cdefg
@@ -109,20 +156,20 @@ mod tests {
             Line {
                 value1: 1,
                 value2: 3,
-                letter: b'a',
-                password: "abcde".as_bytes().to_owned(),
+                letter: "a".to_owned(),
+                password: "abcde".to_owned(),
             },
             Line {
                 value1: 1,
                 value2: 3,
-                letter: b'b',
-                password: "cdefg".as_bytes().to_owned(),
+                letter: "b".to_owned(),
+                password: "cdefg".to_owned(),
             },
             Line {
                 value1: 2,
                 value2: 9,
-                letter: b'c',
-                password: "ccccccccc".as_bytes().to_owned(),
+                letter: "c".to_owned(),
+                password: "ccccccccc".to_owned(),
             },
         ]
     }
@@ -146,4 +193,38 @@ mod tests {
     fn day2_part2() {
         assert_eq!(super::day2_part2(&get_example_data()), 1);
     }
+
+    #[test]
+    fn violations() {
+        assert_eq!(
+            super::violations(&get_example_data(), &[&OccurrenceRange, &PositionXor]),
+            [
+                (1, vec!["occurrence range", "position xor"]),
+                (2, vec!["position xor"])
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_and_multi_char() {
+        let lines = super::generate(
+            "1-2 ż: żółw\n\
+             2-3 ab: xababa\n\
+             1-3 aa: aaaa\n\
+             300-1000 a: aaa",
+        );
+        assert_eq!(
+            super::violations(&lines, &[&OccurrenceRange, &PositionXor]),
+            [
+                (2, vec!["position xor"]),
+                (3, vec!["occurrence range", "position xor"])
+            ]
+        );
+    }
+
+    #[test]
+    fn bound_overflow() {
+        assert!(Line::try_from("1-99999999999999999999999 a: a").is_err());
+        assert!(Line::try_from("+1-3 a: a").is_err());
+    }
 }
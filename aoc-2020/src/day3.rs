@@ -1,11 +1,16 @@
 use super::{aoc, aoc_generator};
 use aoc_utils::try_from_lines;
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum Field {
+pub enum Field {
     Empty,
     Tree,
+    /// any other printable character on the map
+    Other(u8),
 }
 
 #[derive(Debug, PartialEq)]
@@ -19,9 +24,10 @@ impl TryFrom<&str> for Line {
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         s.as_bytes()
             .iter()
-            .map(|c| match c {
+            .map(|&c| match c {
                 b'.' => Ok(Field::Empty),
                 b'#' => Ok(Field::Tree),
+                c if c.is_ascii_graphic() => Ok(Field::Other(c)),
                 _ => Err(()),
             })
             .collect::<Result<Vec<_>, _>>()
@@ -29,26 +35,144 @@ impl TryFrom<&str> for Line {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SlopeError {
+    /// rows must all have the same width for the map to wrap around
+    Ragged {
+        row: usize,
+        width: usize,
+        expected: usize,
+    },
+    EmptyRows,
+    NotDownwards {
+        dy: isize,
+    },
+}
+
+impl fmt::Display for SlopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlopeError::Ragged {
+                row,
+                width,
+                expected,
+            } => write!(f, "row {} has width {}, expected {}", row, width, expected),
+            SlopeError::EmptyRows => write!(f, "map rows are empty"),
+            SlopeError::NotDownwards { dy } => {
+                write!(f, "slope must go downwards, got dy = {}", dy)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SlopeError {}
+
+/// Width shared by all rows of the map.
+fn map_width(lines: &[Line]) -> Result<usize, SlopeError> {
+    let expected = match lines.first() {
+        Some(line) => line.slope.len(),
+        None => return Ok(0),
+    };
+    if expected == 0 {
+        return Err(SlopeError::EmptyRows);
+    }
+    match lines.iter().position(|line| line.slope.len() != expected) {
+        Some(row) => Err(SlopeError::Ragged {
+            row,
+            width: lines[row].slope.len(),
+            expected,
+        }),
+        None => Ok(expected),
+    }
+}
+
 #[aoc_generator(day3)]
 pub fn generate(s: &str) -> Vec<Line> {
-    try_from_lines(s).expect("couldn't parse input")
+    let lines = try_from_lines(s).expect("couldn't parse input");
+    map_width(&lines).expect("invalid map");
+    lines
 }
 
-fn count_slope_trees(lines: &[Line], dx: isize, dy: isize) -> usize {
-    lines
-        .iter()
-        .enumerate()
-        .filter(|&(y, line)| {
-            y as isize % dy == 0
-                && line.slope[((y as isize * dx / dy) % line.slope.len() as isize) as usize]
-                    == Field::Tree
-        })
-        .count()
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Traversal {
+    /// `(x, y)` positions, starting from the top left corner
+    pub visited: Vec<(usize, usize)>,
+    pub encountered: HashMap<Field, usize>,
+}
+
+impl Traversal {
+    pub fn count(&self, field: Field) -> usize {
+        self.encountered.get(&field).copied().unwrap_or(0)
+    }
+}
+
+/// Moves `dx` to the right (wrapping around horizontally) and `dy` down at each step,
+/// until falling off the bottom of the map.
+pub fn traverse(lines: &[Line], dx: isize, dy: isize) -> Result<Traversal, SlopeError> {
+    if dy <= 0 {
+        return Err(SlopeError::NotDownwards { dy });
+    }
+    let width = map_width(lines)?;
+    let mut traversal = Traversal::default();
+    if width == 0 {
+        return Ok(traversal);
+    }
+    // reduced first so that huge `dx` can't overflow
+    let step = dx.rem_euclid(width as isize) as usize;
+
+    let mut x = 0;
+    for (y, line) in lines.iter().enumerate().step_by(dy as usize) {
+        let field = line.slope[x];
+        traversal.visited.push((x, y));
+        *traversal.encountered.entry(field).or_default() += 1;
+        x = (x + step) % width;
+    }
+
+    Ok(traversal)
+}
+
+fn count_slope_trees(lines: &[Line], dx: isize, dy: isize) -> Result<usize, SlopeError> {
+    Ok(traverse(lines, dx, dy)?.count(Field::Tree))
+}
+
+/// `(dx, dy)` moved at each step.
+pub type Slope = (isize, isize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    FewestTrees,
+    MostTrees,
+}
+
+/// Slope with the fewest or most trees among all combinations of `dxs` and `dys`, with the
+/// number of trees. Ties are resolved in favour of smaller `dx`, then smaller `dy`.
+pub fn best_slope(
+    lines: &[Line],
+    dxs: RangeInclusive<isize>,
+    dys: RangeInclusive<isize>,
+    goal: Goal,
+) -> Result<Option<(Slope, usize)>, SlopeError> {
+    let mut best: Option<(Slope, usize)> = None;
+    for dx in dxs {
+        for dy in dys.clone() {
+            let trees = count_slope_trees(lines, dx, dy)?;
+            let better = match (best, goal) {
+                (None, _) => true,
+                (Some((_, best)), Goal::FewestTrees) => trees < best,
+                (Some((_, best)), Goal::MostTrees) => trees > best,
+            };
+            if better {
+                best = Some(((dx, dy), trees));
+            }
+        }
+    }
+
+    Ok(best)
 }
 
 #[aoc(day3, part1)]
 pub fn day3_part1(lines: &[Line]) -> usize {
-    count_slope_trees(lines, 3, 1)
+    count_slope_trees(lines, 3, 1).unwrap()
 }
 
 #[aoc(day3, part2)]
@@ -57,13 +181,13 @@ pub fn day3_part2(lines: &[Line]) -> usize {
 
     params
         .iter()
-        .map(|&(dx, dy)| count_slope_trees(lines, dx, dy))
+        .map(|&(dx, dy)| count_slope_trees(lines, dx, dy).unwrap())
         .product()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Line;
+    use super::{Field, Goal, Line, SlopeError};
 
     const EXAMPLE_INPUT: &str = r"..##.......
 #...#...#..
@@ -130,4 +254,110 @@ mod tests {
     fn day3_part2() {
         assert_eq!(super::day3_part2(&get_example_data()), 336);
     }
+
+    #[test]
+    fn steep_slopes() {
+        let data = get_example_data();
+        let traversal = super::traverse(&data, 1, 3).unwrap();
+        assert_eq!(traversal.visited, [(0, 0), (1, 3), (2, 6), (3, 9)]);
+        assert_eq!(traversal.count(Field::Tree), 0);
+        assert_eq!(traversal.count(Field::Empty), 4);
+
+        let traversal = super::traverse(&data, 2, 3).unwrap();
+        assert_eq!(traversal.visited, [(0, 0), (2, 3), (4, 6), (6, 9)]);
+        assert_eq!(traversal.count(Field::Tree), 1);
+
+        let traversal = super::traverse(&data, -4, 2).unwrap();
+        assert_eq!(
+            traversal.visited,
+            [(0, 0), (7, 2), (3, 4), (10, 6), (6, 8), (2, 10)]
+        );
+    }
+
+    #[test]
+    fn huge_slopes() {
+        let data = get_example_data();
+        for dx in [isize::MAX, isize::MIN, isize::MAX - 3] {
+            let equivalent = dx.rem_euclid(11);
+            assert_eq!(
+                super::traverse(&data, dx, 1),
+                super::traverse(&data, equivalent, 1)
+            );
+        }
+        let traversal = super::traverse(&data, isize::MIN, isize::MAX).unwrap();
+        assert_eq!(traversal.visited, [(0, 0)]);
+    }
+
+    #[test]
+    fn other_objects() {
+        let data = super::generate("..\n.$\n#.\n.$");
+        let traversal = super::traverse(&data, 1, 1).unwrap();
+        assert_eq!(traversal.count(Field::Other(b'$')), 2);
+        assert_eq!(traversal.count(Field::Tree), 1);
+        assert_eq!(traversal.count(Field::Empty), 1);
+    }
+
+    #[test]
+    fn best_slope() {
+        let data = get_example_data();
+        assert_eq!(
+            super::best_slope(&data, 1..=7, 1..=2, Goal::MostTrees),
+            Ok(Some(((3, 1), 7)))
+        );
+        assert_eq!(
+            super::best_slope(&data, 1..=7, 1..=2, Goal::FewestTrees),
+            Ok(Some(((5, 2), 0)))
+        );
+        assert_eq!(
+            super::best_slope(&data, 1..=4, 1..=2, Goal::FewestTrees),
+            Ok(Some(((2, 1), 1)))
+        );
+    }
+
+    #[test]
+    fn invalid_slopes() {
+        let data = get_example_data();
+        assert_eq!(
+            super::traverse(&data, 1, 0),
+            Err(SlopeError::NotDownwards { dy: 0 })
+        );
+        assert_eq!(
+            super::best_slope(&data, 1..=3, -1..=2, Goal::MostTrees),
+            Err(SlopeError::NotDownwards { dy: -1 })
+        );
+    }
+
+    #[test]
+    fn invalid_maps() {
+        let ragged = ["..#", "#.", "..."]
+            .iter()
+            .map(|&line| Line::try_from(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            super::traverse(&ragged, 1, 1),
+            Err(SlopeError::Ragged {
+                row: 1,
+                width: 2,
+                expected: 3
+            })
+        );
+        assert_eq!(
+            super::best_slope(&ragged, 1..=1, 1..=1, Goal::FewestTrees),
+            Err(SlopeError::Ragged {
+                row: 1,
+                width: 2,
+                expected: 3
+            })
+        );
+
+        let empty = vec![Line { slope: vec![] }];
+        assert_eq!(super::traverse(&empty, 1, 1), Err(SlopeError::EmptyRows));
+        assert_eq!(super::traverse(&[], 1, 1).unwrap().visited, []);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid map")]
+    fn generate_ragged() {
+        super::generate("..#\n#.\n...");
+    }
 }
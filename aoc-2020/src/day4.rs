@@ -1,5 +1,7 @@
 use super::{aoc, aoc_generator};
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
 
 #[derive(Debug, PartialEq)]
 pub struct Passport {
@@ -30,89 +32,191 @@ pub fn generate(s: &str) -> Vec<Passport> {
     passports
 }
 
-#[aoc(day4, part1)]
-pub fn day4_part1(passports: &[Passport]) -> usize {
-    let required_fields = &[
-        "byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid",
-        // "cid",
-    ];
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    Any,
+    Range(RangeInclusive<i64>),
+    /// number followed by one of the unit suffixes, each with its own range
+    WithUnit(Vec<(&'static str, RangeInclusive<i64>)>),
+    /// `#` followed by six lowercase hex digits
+    HexColor,
+    OneOf(Vec<&'static str>),
+    Digits(usize),
+}
 
-    passports
-        .iter()
-        .filter(|p| required_fields.iter().all(|&f| p.fields.contains_key(f)))
-        .count()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    NotANumber,
+    OutOfRange(RangeInclusive<i64>),
+    UnknownUnit,
+    NotAHexColor,
+    NotOneOf(Vec<&'static str>),
+    WrongLength { expected: usize, found: usize },
+    NotDigits,
 }
 
-#[aoc(day4, part2)]
-pub fn day4_part2(passports: &[Passport]) -> usize {
-    fn validate(passport: &Passport) -> bool {
-        let required_fields = &[
-            "byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid",
-            // "cid",
-        ];
-
-        passport
-            .fields
-            .iter()
-            .filter_map(|(key, value)| {
-                let valid = match key.as_str() {
-                    "byr" => (1920i32..=2002).contains(&value.parse().ok()?),
-                    "iyr" => (2010i32..=2020).contains(&value.parse().ok()?),
-                    "eyr" => (2020i32..=2030).contains(&value.parse().ok()?),
-                    "hgt" => {
-                        let (v, unit) = value.split_at(value.len().saturating_sub(2));
-                        match unit {
-                            "cm" => (150i32..=193).contains(&v.parse().ok()?),
-                            "in" => (59i32..=76).contains(&v.parse().ok()?),
-                            _ => false,
-                        }
-                    }
-                    "hcl" => {
-                        let mut chars = value.chars();
-                        let starts_with_hash = chars.next()? == '#';
-                        let hex_digits = chars
-                            .by_ref()
-                            .take(6)
-                            .filter(|c| matches!(c, '0'..='9' | 'a'..='f'))
-                            .count()
-                            == 6;
-                        let six_digits = chars.next().is_none();
-                        starts_with_hash && hex_digits && six_digits
-                    }
-                    "ecl" => matches!(
-                        value.as_str(),
-                        "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth"
-                    ),
-                    "pid" => value.len() == 9 && value.chars().all(|c| matches!(c, '0'..='9')),
-                    _ => false,
-                };
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::NotANumber => write!(f, "not a number"),
+            Reason::OutOfRange(range) => {
+                write!(f, "not between {} and {}", range.start(), range.end())
+            }
+            Reason::UnknownUnit => write!(f, "missing or unknown unit"),
+            Reason::NotAHexColor => write!(f, "not a hex colour"),
+            Reason::NotOneOf(values) => write!(f, "not one of {}", values.join(", ")),
+            Reason::WrongLength { expected, found } => {
+                write!(f, "expected {} characters, found {}", expected, found)
+            }
+            Reason::NotDigits => write!(f, "not all digits"),
+        }
+    }
+}
 
-                if valid {
-                    Some(())
-                } else {
-                    // println!("invalid: {}: {}", key, value);
-                    None
+impl Rule {
+    pub fn check(&self, value: &str) -> Result<(), Reason> {
+        fn in_range(value: &str, range: &RangeInclusive<i64>) -> Result<(), Reason> {
+            let value = value.parse().map_err(|_| Reason::NotANumber)?;
+            if range.contains(&value) {
+                Ok(())
+            } else {
+                Err(Reason::OutOfRange(range.clone()))
+            }
+        }
+
+        match self {
+            Rule::Any => Ok(()),
+            Rule::Range(range) => in_range(value, range),
+            Rule::WithUnit(units) => units
+                .iter()
+                .find_map(|(unit, range)| Some((value.strip_suffix(unit)?, range)))
+                .ok_or(Reason::UnknownUnit)
+                .and_then(|(value, range)| in_range(value, range)),
+            Rule::HexColor => match value.strip_prefix('#') {
+                Some(hex)
+                    if hex.len() == 6
+                        && hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')) =>
+                {
+                    Ok(())
                 }
+                _ => Err(Reason::NotAHexColor),
+            },
+            Rule::OneOf(values) if values.contains(&value) => Ok(()),
+            Rule::OneOf(values) => Err(Reason::NotOneOf(values.clone())),
+            Rule::Digits(len) if value.len() != *len => Err(Reason::WrongLength {
+                expected: *len,
+                found: value.len(),
+            }),
+            Rule::Digits(_) if value.bytes().all(|c| c.is_ascii_digit()) => Ok(()),
+            Rule::Digits(_) => Err(Reason::NotDigits),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    Missing,
+    Invalid { value: String, reason: Reason },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSpec {
+    name: &'static str,
+    required: bool,
+    rule: Rule,
+}
+
+/// Fields expected in a passport. Fields not described by the schema are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Schema {
+    fields: Vec<FieldSpec>,
+}
+
+impl Schema {
+    pub fn required(mut self, name: &'static str, rule: Rule) -> Self {
+        self.fields.push(FieldSpec {
+            name,
+            required: true,
+            rule,
+        });
+        self
+    }
+
+    pub fn optional(mut self, name: &'static str, rule: Rule) -> Self {
+        self.fields.push(FieldSpec {
+            name,
+            required: false,
+            rule,
+        });
+        self
+    }
+
+    /// Problems with the passport's fields, in schema order. Empty if the passport is valid.
+    pub fn validate(&self, passport: &Passport) -> Vec<(&'static str, FieldError)> {
+        self.fields
+            .iter()
+            .filter_map(|spec| {
+                let error = match passport.fields.get(spec.name) {
+                    None if spec.required => FieldError::Missing,
+                    None => return None,
+                    Some(value) => FieldError::Invalid {
+                        value: value.clone(),
+                        reason: spec.rule.check(value).err()?,
+                    },
+                };
+                Some((spec.name, error))
             })
-            .count()
-            == required_fields.len()
+            .collect()
     }
 
-    passports
-        .iter()
-        .filter(|p| {
-            let valid = validate(p);
-            if false {
-                print!("{}: ", if valid { "valid" } else { "invalid" });
-                println!("{:#?}", p);
-            }
-            valid
+    pub fn is_valid(&self, passport: &Passport) -> bool {
+        self.validate(passport).is_empty()
+    }
+}
+
+pub fn presence_schema() -> Schema {
+    ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"]
+        .into_iter()
+        .fold(Schema::default(), |schema, name| {
+            schema.required(name, Rule::Any)
         })
-        .count()
+        .optional("cid", Rule::Any)
+}
+
+pub fn strict_schema() -> Schema {
+    Schema::default()
+        .required("byr", Rule::Range(1920..=2002))
+        .required("iyr", Rule::Range(2010..=2020))
+        .required("eyr", Rule::Range(2020..=2030))
+        .required(
+            "hgt",
+            Rule::WithUnit(vec![("cm", 150..=193), ("in", 59..=76)]),
+        )
+        .required("hcl", Rule::HexColor)
+        .required(
+            "ecl",
+            Rule::OneOf(vec!["amb", "blu", "brn", "gry", "grn", "hzl", "oth"]),
+        )
+        .required("pid", Rule::Digits(9))
+        .optional("cid", Rule::Any)
+}
+
+#[aoc(day4, part1)]
+pub fn day4_part1(passports: &[Passport]) -> usize {
+    let schema = presence_schema();
+    passports.iter().filter(|p| schema.is_valid(p)).count()
+}
+
+#[aoc(day4, part2)]
+pub fn day4_part2(passports: &[Passport]) -> usize {
+    let schema = strict_schema();
+    passports.iter().filter(|p| schema.is_valid(p)).count()
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{FieldError, Reason};
+
     const EXAMPLE_INPUT: &str = r"ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
 byr:1937 iyr:2017 cid:147 hgt:183cm
 
@@ -177,4 +281,63 @@ iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719";
     fn day4_part2_valid() {
         assert_eq!(super::day4_part2(&super::generate(VALID_INPUT)), 4);
     }
+
+    #[test]
+    fn diagnostics() {
+        let passports = super::generate(INVALID_INPUT);
+        let schema = super::strict_schema();
+
+        assert_eq!(
+            schema.validate(&passports[0]),
+            [
+                (
+                    "eyr",
+                    FieldError::Invalid {
+                        value: "1972".to_owned(),
+                        reason: Reason::OutOfRange(2020..=2030),
+                    }
+                ),
+                (
+                    "hgt",
+                    FieldError::Invalid {
+                        value: "170".to_owned(),
+                        reason: Reason::UnknownUnit,
+                    }
+                ),
+                (
+                    "pid",
+                    FieldError::Invalid {
+                        value: "186cm".to_owned(),
+                        reason: Reason::WrongLength {
+                            expected: 9,
+                            found: 5,
+                        },
+                    }
+                ),
+            ]
+        );
+
+        let errors = schema.validate(&passports[3]);
+        assert_eq!(
+            errors.iter().map(|(field, _)| *field).collect::<Vec<_>>(),
+            ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"]
+        );
+        assert_eq!(
+            errors[5].1,
+            FieldError::Invalid {
+                value: "zzz".to_owned(),
+                reason: Reason::NotOneOf(vec!["amb", "blu", "brn", "gry", "grn", "hzl", "oth"]),
+            }
+        );
+
+        let passports = super::generate(EXAMPLE_INPUT);
+        assert_eq!(
+            super::presence_schema().validate(&passports[1]),
+            [("hgt", FieldError::Missing)]
+        );
+        assert_eq!(
+            super::strict_schema().validate(&passports[3]),
+            [("byr", FieldError::Missing)]
+        );
+    }
 }
@@ -1,48 +1,186 @@
 use super::{aoc, aoc_generator};
+use aoc_utils::libs::itertools::Itertools;
 use aoc_utils::try_from_lines;
 use std::convert::TryFrom;
+use std::fmt;
 
+/// Number of binary partitioning steps used for rows (`F`/`B`) and columns (`L`/`R`).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum BinaryPartition {
-    Lower,
-    Upper,
+pub struct Geometry {
+    row_bits: u32,
+    col_bits: u32,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry {
+            row_bits: 7,
+            col_bits: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Seat {
+    pub row: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecodeError {
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+    InvalidChar {
+        position: usize,
+        found: char,
+        expected: [char; 2],
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::WrongLength { expected, found } => {
+                write!(f, "expected {} characters, found {}", expected, found)
+            }
+            DecodeError::InvalidChar {
+                position,
+                found,
+                expected: [lower, upper],
+            } => write!(
+                f,
+                "invalid character {:?} at position {}, expected {:?} or {:?}",
+                found, position, lower, upper
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Geometry {
+    const ROW_CHARS: [char; 2] = ['F', 'B'];
+    const COL_CHARS: [char; 2] = ['L', 'R'];
+
+    pub fn new(row_bits: u32, col_bits: u32) -> Self {
+        assert!(
+            matches!(row_bits.checked_add(col_bits), Some(bits) if bits < usize::BITS),
+            "too many seats"
+        );
+        Geometry { row_bits, col_bits }
+    }
+
+    pub fn row_bits(&self) -> u32 {
+        self.row_bits
+    }
+
+    pub fn col_bits(&self) -> u32 {
+        self.col_bits
+    }
+
+    pub fn rows(&self) -> usize {
+        1 << self.row_bits
+    }
+
+    pub fn cols(&self) -> usize {
+        1 << self.col_bits
+    }
+
+    pub fn seats(&self) -> usize {
+        self.rows() * self.cols()
+    }
+
+    pub fn seat_id(&self, seat: Seat) -> usize {
+        seat.row * self.cols() + seat.col
+    }
+
+    pub fn seat(&self, id: usize) -> Option<Seat> {
+        (id < self.seats()).then(|| Seat {
+            row: id / self.cols(),
+            col: id % self.cols(),
+        })
+    }
+
+    pub fn decode(&self, pass: &str) -> Result<Seat, DecodeError> {
+        let expected = (self.row_bits + self.col_bits) as usize;
+        let found = pass.chars().count();
+        if found != expected {
+            return Err(DecodeError::WrongLength { expected, found });
+        }
+
+        let mut seat = Seat { row: 0, col: 0 };
+        for (position, c) in pass.chars().enumerate() {
+            let (value, chars) = if position < self.row_bits as usize {
+                (&mut seat.row, Self::ROW_CHARS)
+            } else {
+                (&mut seat.col, Self::COL_CHARS)
+            };
+            let bit = chars.iter().position(|&expected| expected == c).ok_or(
+                DecodeError::InvalidChar {
+                    position,
+                    found: c,
+                    expected: chars,
+                },
+            )?;
+            *value = *value * 2 + bit;
+        }
+
+        Ok(seat)
+    }
+
+    pub fn encode(&self, seat: Seat) -> Option<String> {
+        if seat.row >= self.rows() || seat.col >= self.cols() {
+            return None;
+        }
+
+        let bits = |value: usize, count: u32, chars: [char; 2]| {
+            (0..count).rev().map(move |bit| chars[(value >> bit) & 1])
+        };
+        Some(
+            bits(seat.row, self.row_bits, Self::ROW_CHARS)
+                .chain(bits(seat.col, self.col_bits, Self::COL_CHARS))
+                .collect(),
+        )
+    }
+
+    /// Ids of all seats in the plane that don't appear in `ids`, in increasing order.
+    /// Ids outside of the plane are ignored.
+    ///
+    /// Only the given ids are stored, so this works for planes too big to allocate.
+    pub fn missing_seats(
+        &self,
+        ids: impl IntoIterator<Item = usize>,
+    ) -> impl Iterator<Item = usize> {
+        let seats = self.seats();
+        let mut taken: Vec<usize> = ids.into_iter().filter(|&id| id < seats).collect();
+        taken.sort_unstable();
+        taken.dedup();
+
+        // gaps between consecutive taken ids, with sentinels around the plane
+        let bounds = std::iter::once(None)
+            .chain(taken.into_iter().map(Some))
+            .chain(std::iter::once(Some(seats)));
+        bounds
+            .tuple_windows()
+            .flat_map(|(prev, next): (Option<usize>, Option<usize>)| {
+                prev.map_or(0, |prev| prev + 1)..next.unwrap()
+            })
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Line {
-    row_partitions: Vec<BinaryPartition>,
-    col_partitions: Vec<BinaryPartition>,
+    seat: Seat,
 }
 
 impl TryFrom<&str> for Line {
-    type Error = ();
+    type Error = DecodeError;
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        let s = s.as_bytes();
-        let row_partitions = s
-            .get(..7)
-            .ok_or(())?
-            .iter()
-            .map(|c| match c {
-                b'F' => Ok(BinaryPartition::Lower),
-                b'B' => Ok(BinaryPartition::Upper),
-                _ => Err(()),
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        let col_partitions = s
-            .get(7..10)
-            .ok_or(())?
-            .iter()
-            .map(|c| match c {
-                b'L' => Ok(BinaryPartition::Lower),
-                b'R' => Ok(BinaryPartition::Upper),
-                _ => Err(()),
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
         Ok(Line {
-            row_partitions,
-            col_partitions,
+            seat: Geometry::default().decode(s)?,
         })
     }
 }
@@ -52,29 +190,8 @@ pub fn generate(s: &str) -> Vec<Line> {
     try_from_lines(s).expect("couldn't parse input")
 }
 
-fn partition(
-    mut lower_bound: usize,
-    mut upper_bound: usize,
-    partitions: &[BinaryPartition],
-) -> Option<usize> {
-    for part in partitions {
-        match part {
-            BinaryPartition::Lower => upper_bound = (lower_bound + upper_bound) / 2,
-            BinaryPartition::Upper => lower_bound = (lower_bound + upper_bound) / 2,
-        }
-    }
-
-    if lower_bound + 1 == upper_bound {
-        Some(lower_bound)
-    } else {
-        None
-    }
-}
-
 fn seat_id(line: &Line) -> usize {
-    let row_id = partition(0, 128, &line.row_partitions).unwrap();
-    let col_id = partition(0, 8, &line.col_partitions).unwrap();
-    row_id * 8 + col_id
+    Geometry::default().seat_id(line.seat)
 }
 
 #[aoc(day5, part1)]
@@ -84,13 +201,17 @@ pub fn day5_part1(lines: &[Line]) -> usize {
 
 #[aoc(day5, part2)]
 pub fn day5_part2(lines: &[Line]) -> usize {
-    use aoc_utils::libs::itertools::Itertools;
-    lines
+    let missing: Vec<_> = Geometry::default()
+        .missing_seats(lines.iter().map(seat_id))
+        .collect();
+    missing
         .iter()
-        .map(seat_id)
-        .sorted()
-        .tuple_windows()
-        .find_map(|(a, b)| if a + 1 != b { Some(a + 1) } else { None })
+        .find(|&&id| {
+            id > 0
+                && missing.binary_search(&(id - 1)).is_err()
+                && missing.binary_search(&(id + 1)).is_err()
+        })
+        .copied()
         .unwrap()
 }
 
@@ -98,6 +219,8 @@ pub fn day5_part2(lines: &[Line]) -> usize {
 mod tests {
     use std::convert::TryInto;
 
+    use super::{DecodeError, Geometry, Seat};
+
     const EXAMPLE_DATA: &str = r"FBFBBFFRLR
 BFFFBBFRRR
 FFFBBBFRRR
@@ -115,4 +238,93 @@ BBFFBBFRLL";
     fn day5_part1() {
         assert_eq!(super::day5_part1(&super::generate(EXAMPLE_DATA)), 820);
     }
+
+    #[test]
+    fn roundtrip() {
+        for geometry in [
+            Geometry::default(),
+            Geometry::new(3, 5),
+            Geometry::new(0, 2),
+        ] {
+            for id in 0..geometry.seats() {
+                let seat = geometry.seat(id).unwrap();
+                let pass = geometry.encode(seat).unwrap();
+                assert_eq!(geometry.decode(&pass), Ok(seat));
+                assert_eq!(geometry.seat_id(seat), id);
+            }
+            assert_eq!(geometry.seat(geometry.seats()), None);
+        }
+
+        assert_eq!(
+            Geometry::default()
+                .encode(Seat { row: 70, col: 7 })
+                .as_deref(),
+            Some("BFFFBBFRRR")
+        );
+        assert_eq!(Geometry::default().encode(Seat { row: 128, col: 0 }), None);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let geometry = Geometry::default();
+        assert_eq!(
+            geometry.decode("FBFBBFFRL"),
+            Err(DecodeError::WrongLength {
+                expected: 10,
+                found: 9
+            })
+        );
+        assert_eq!(
+            geometry.decode("FBFBBFLRLR"),
+            Err(DecodeError::InvalidChar {
+                position: 6,
+                found: 'L',
+                expected: ['F', 'B'],
+            })
+        );
+        assert_eq!(
+            geometry.decode("FBFBBFFRLż"),
+            Err(DecodeError::InvalidChar {
+                position: 9,
+                found: 'ż',
+                expected: ['L', 'R'],
+            })
+        );
+    }
+
+    #[test]
+    fn missing_seats() {
+        let geometry = Geometry::new(2, 1);
+        let missing = |ids: &[usize]| geometry.missing_seats(ids.to_vec()).collect::<Vec<_>>();
+        assert_eq!(missing(&[0, 2, 3, 6]), [1, 4, 5, 7]);
+        assert_eq!(missing(&[6, 3, 3, 2, 0, 100]), [1, 4, 5, 7]);
+        assert_eq!(missing(&[]), (0..8).collect::<Vec<_>>());
+        assert_eq!(missing(&[1, 2, 3, 4, 5, 6, 7]), [0]);
+
+        // only the given ids are stored
+        let huge = Geometry::new(40, 20);
+        assert_eq!(huge.row_bits(), 40);
+        assert_eq!(huge.col_bits(), 20);
+        let mut missing = huge.missing_seats([0, 1, 3]);
+        assert_eq!(missing.by_ref().take(3).collect::<Vec<_>>(), [2, 4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many seats")]
+    fn too_many_seats() {
+        Geometry::new(u32::MAX, 1);
+    }
+
+    #[test]
+    fn day5_part2() {
+        let lines = (0..20)
+            .filter(|&id| id != 13)
+            .map(|id| {
+                Geometry::default()
+                    .encode(Geometry::default().seat(id + 40).unwrap())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(super::day5_part2(&super::generate(&lines.join("\n"))), 53);
+    }
 }
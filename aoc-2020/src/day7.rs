@@ -1,10 +1,10 @@
 use super::{aoc, aoc_generator};
+use anyhow::bail;
 use aoc_utils::libs::*;
 use aoc_utils::try_from_lines;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::cell::Cell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 
 type Color = String;
@@ -38,7 +38,7 @@ impl TryFrom<&str> for Line {
             .unwrap()
         });
         static CONTAINEES_PATTERN: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"(?P<capacity>\d) (?P<color>[\w ]+) bags?").unwrap());
+            Lazy::new(|| Regex::new(r"(?P<capacity>\d+) (?P<color>[\w ]+) bags?").unwrap());
 
         let m = WHOLE_PATTERN.captures(s).ok_or(())?;
         let container = m.name("container").unwrap().as_str().to_owned();
@@ -48,15 +48,13 @@ impl TryFrom<&str> for Line {
                 .map(|containee| {
                     let capacity = containee
                         .name("capacity")
-                        .map(|m| m.as_str())
-                        .and_then(|s| s.chars().next())
-                        .and_then(|c| c.to_digit(10))
-                        .unwrap();
+                        .and_then(|m| m.as_str().parse().ok())
+                        .ok_or(())?;
                     let color = containee.name("color").unwrap().as_str().to_owned();
 
-                    ContaineeData { color, capacity }
+                    Ok(ContaineeData { color, capacity })
                 })
-                .collect()
+                .collect::<Result<_, _>>()?
         } else {
             vec![]
         };
@@ -73,107 +71,117 @@ pub fn generate(s: &str) -> Vec<Line> {
     try_from_lines(s).expect("couldn't parse input")
 }
 
-type ContaineeContainerGraph = HashMap<Color, (Cell<bool>, Vec<ContainerData>)>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
 
-fn create_containee_container_graph(lines: &[Line]) -> ContaineeContainerGraph {
-    let mut graph = Default::default();
-    if false {
-        return graph; // type inference
-    }
+/// Bag containment rules, answering queries about any colour.
+#[derive(Debug, Clone, Default)]
+pub struct BagRules {
+    contents: HashMap<Color, Vec<ContaineeData>>,
+    containers: HashMap<Color, Vec<ContainerData>>,
+    /// number of bags transitively inside a bag of each colour
+    totals: HashMap<Color, usize>,
+}
 
-    for Line {
-        container,
-        containees,
-    } in lines
-    {
-        let mut current_can_contain = graph.entry(container.clone()).or_default().0.get();
-        for ContaineeData { color, capacity } in containees {
-            let (containee_can_contain, containee_containers) =
-                graph.entry(color.clone()).or_default();
-            if containee_can_contain.get() {
-                current_can_contain = true;
+impl BagRules {
+    /// Fails if a colour is given more than one rule or if the rules are cyclic.
+    pub fn new(lines: &[Line]) -> anyhow::Result<Self> {
+        let mut rules = BagRules::default();
+
+        for Line {
+            container,
+            containees,
+        } in lines
+        {
+            if rules
+                .contents
+                .insert(container.clone(), containees.clone())
+                .is_some()
+            {
+                bail!("duplicate rule for {} bags", container);
+            }
+            for ContaineeData { color, capacity } in containees {
+                rules
+                    .containers
+                    .entry(color.clone())
+                    .or_default()
+                    .push(ContainerData {
+                        color: container.clone(),
+                        capacity: *capacity,
+                    });
             }
-            containee_containers.push(ContainerData {
-                color: container.clone(),
-                capacity: *capacity,
-            })
         }
-        fill_containment_dfs(
-            &graph,
-            container,
-            current_can_contain || container == "shiny gold",
-        );
 
-        // println!("{:#?}", graph);
+        let mut visits = HashMap::new();
+        for color in rules.contents.keys() {
+            Self::fill_totals(&rules.contents, &mut rules.totals, &mut visits, color)?;
+        }
+
+        Ok(rules)
     }
 
-    fn fill_containment_dfs(
-        graph: &ContaineeContainerGraph,
-        color: &str,
-        current_can_contain: bool,
-    ) {
-        let (can_contain, containers) = &graph[color];
-        if current_can_contain {
-            can_contain.set(true);
+    fn fill_totals<'a>(
+        contents: &'a HashMap<Color, Vec<ContaineeData>>,
+        totals: &mut HashMap<Color, usize>,
+        visits: &mut HashMap<&'a str, Visit>,
+        color: &'a str,
+    ) -> anyhow::Result<usize> {
+        match visits.get(color) {
+            Some(Visit::Done) => return Ok(totals[color]),
+            Some(Visit::InProgress) => bail!("cyclic rules: {} bags contain themselves", color),
+            None => {}
         }
-        for container in containers {
-            fill_containment_dfs(
-                graph,
-                &container.color,
-                current_can_contain || can_contain.get(),
-            );
+        visits.insert(color, Visit::InProgress);
+
+        let mut total = 0;
+        for ContaineeData { color, capacity } in contents.get(color).into_iter().flatten() {
+            total += *capacity as usize * (Self::fill_totals(contents, totals, visits, color)? + 1);
         }
-    }
 
-    // println!("{:#?}", graph);
+        visits.insert(color, Visit::Done);
+        totals.insert(color.to_owned(), total);
+        Ok(total)
+    }
 
-    graph
-}
+    /// Colours of all bags that can eventually contain a bag of the given colour.
+    pub fn containers_of(&self, color: &str) -> HashSet<&str> {
+        let mut found = HashSet::new();
+        let mut to_visit = vec![color];
+        while let Some(current) = to_visit.pop() {
+            for container in self.containers.get(current).into_iter().flatten() {
+                if found.insert(container.color.as_str()) {
+                    to_visit.push(&container.color);
+                }
+            }
+        }
+        found
+    }
 
-type ContainerContaineeGraph = HashMap<Color, Vec<ContaineeData>>;
-
-fn create_container_containee_graph(lines: &[Line]) -> ContainerContaineeGraph {
-    lines
-        .iter()
-        .map(
-            |Line {
-                 container,
-                 containees,
-             }| (container.clone(), containees.clone()),
-        )
-        .collect()
+    /// Number of bags inside a bag of the given colour, `None` if the colour is unknown.
+    pub fn contained_count(&self, color: &str) -> Option<usize> {
+        self.totals.get(color).copied()
+    }
 }
 
 #[aoc(day7, part1)]
 pub fn day7_part1(lines: &[Line]) -> usize {
-    let graph = create_containee_container_graph(lines);
-    graph
-        .iter()
-        .filter(|&(color, (can_contain_shiny_gold, _))| {
-            color != "shiny gold" && can_contain_shiny_gold.get()
-        })
-        .count()
+    let rules = BagRules::new(lines).expect("invalid rules");
+    rules.containers_of("shiny gold").len()
 }
 
 #[aoc(day7, part2)]
 pub fn day7_part2(lines: &[Line]) -> usize {
-    let graph = create_container_containee_graph(lines);
-    fn count_dfs(graph: &ContainerContaineeGraph, container: &str) -> usize {
-        graph[container]
-            .iter()
-            .map(|ContaineeData { color, capacity }| {
-                *capacity as usize * (count_dfs(graph, color) + 1)
-            })
-            .sum()
-    }
-
-    count_dfs(&graph, "shiny gold")
+    let rules = BagRules::new(lines).expect("invalid rules");
+    rules.contained_count("shiny gold").unwrap_or(0)
 }
 
 #[cfg(test)]
 mod tests {
     use super::itertools::Itertools;
-    use super::{ContaineeData, Line};
+    use super::{BagRules, ContaineeData, Line};
 
     const EXAMPLE_INPUT: &str = r"light red bags contain 1 bright white bag, 2 muted yellow bags.
 dark orange bags contain 3 bright white bags, 4 muted yellow bags.
@@ -250,4 +258,51 @@ dark violet bags contain no other bags.";
         assert_eq!(super::day7_part2(&super::generate(EXAMPLE_INPUT)), 32);
         assert_eq!(super::day7_part2(&super::generate(EXAMPLE_INPUT2)), 126);
     }
+
+    #[test]
+    fn any_color() {
+        let rules = BagRules::new(&super::generate(EXAMPLE_INPUT)).unwrap();
+        assert_eq!(
+            rules
+                .containers_of("faded blue")
+                .into_iter()
+                .sorted()
+                .collect_vec(),
+            [
+                "bright white",
+                "dark olive",
+                "dark orange",
+                "light red",
+                "muted yellow",
+                "shiny gold",
+                "vibrant plum",
+            ]
+        );
+        assert!(rules.containers_of("light red").is_empty());
+        assert_eq!(rules.contained_count("faded blue"), Some(0));
+        assert_eq!(rules.contained_count("dark olive"), Some(7));
+        assert_eq!(rules.contained_count("muted yellow"), Some(2 * 33 + 9));
+        assert_eq!(rules.contained_count("light blue"), None);
+    }
+
+    #[test]
+    fn multi_digit() {
+        let lines = super::generate(
+            "shiny gold bags contain 12 dark red bags, 1 pale blue bag.\n\
+             dark red bags contain 100 pale blue bags.\n\
+             pale blue bags contain no other bags.",
+        );
+        assert_eq!(lines[0].containees[0].capacity, 12);
+        assert_eq!(super::day7_part2(&lines), 12 * 101 + 1);
+    }
+
+    #[test]
+    fn cyclic() {
+        let lines = super::generate(
+            "shiny gold bags contain 1 dark red bag.\n\
+             dark red bags contain 2 pale blue bags.\n\
+             pale blue bags contain 3 shiny gold bags.",
+        );
+        assert!(BagRules::new(&lines).is_err());
+    }
 }
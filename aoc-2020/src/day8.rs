@@ -1,123 +1,94 @@
 use super::{aoc, aoc_generator};
+use crate::console::{Acc, Ip, Machine, Op, Termination};
 use aoc_utils::try_from_lines;
-use std::convert::TryFrom;
-
-type Ip = i32;
-type Acc = i32;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Op {
-    Acc(Acc),
-    Jmp(Ip),
-    Nop(Ip),
-}
-
-impl Op {
-    fn invert(&self) -> Op {
-        use Op::*;
-        match *self {
-            Jmp(v) => Nop(v),
-            Nop(v) => Jmp(v),
-            op => op,
-        }
-    }
-}
-
-impl TryFrom<&str> for Op {
-    type Error = ();
-
-    fn try_from(s: &str) -> Result<Self, Self::Error> {
-        let value = s[4..].parse().map_err(|_| ())?;
-
-        Ok(match &s[..3] {
-            "acc" => Op::Acc(value),
-            "jmp" => Op::Jmp(value),
-            "nop" => Op::Nop(value),
-            _ => return Err(()),
-        })
-    }
-}
 
 #[aoc_generator(day8)]
 pub fn generate(s: &str) -> Vec<Op> {
     try_from_lines(s).expect("couldn't parse input")
 }
 
-type ExecResult = Result<Acc, Acc>;
-trait OnVisited: FnOnce(&[Op], Ip, Acc, Vec<bool>, Vec<Ip>) -> Option<ExecResult> {}
-impl<F: FnOnce(&[Op], Ip, Acc, Vec<bool>, Vec<Ip>) -> Option<ExecResult>> OnVisited for F {}
-
-fn on_visited_nop(_: &[Op], _: Ip, _: Acc, _: Vec<bool>, _: Vec<Ip>) -> Option<ExecResult> {
-    None
+#[aoc(day8, part1)]
+pub fn day8_part1(instructions: &[Op]) -> i32 {
+    let mut machine = Machine::new(instructions).with_loop_detection();
+    match machine.run() {
+        Termination::InfiniteLoop { .. } => machine.registers.acc,
+        termination => panic!("program didn't loop: {:?}", termination),
+    }
 }
 
-fn execute(instructions: &[Op], on_visited: impl OnVisited, inverted: Option<Ip>) -> ExecResult {
-    let mut visited = vec![false; instructions.len()];
-    let mut visit_order = Vec::with_capacity(instructions.len());
+/// Single `jmp`/`nop` flip that makes the program halt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    pub ip: Ip,
+    pub original: Op,
+    pub replacement: Op,
+    /// accumulator value after the repaired program halts
+    pub acc: Acc,
+}
 
-    let mut ip = 0i32;
-    let mut acc = 0;
+fn successor(op: Op, ip: Ip) -> Ip {
+    match op {
+        Op::Jmp(offset) => ip + offset,
+        Op::Acc(_) | Op::Nop(_) => ip + 1,
+    }
+}
 
-    while ip < instructions.len() as i32 {
-        if std::mem::replace(&mut visited[ip as usize], true) {
-            return if let Some(value) = on_visited(instructions, ip, acc, visited, visit_order) {
-                value
-            } else {
-                Err(acc)
-            };
+/// Finds the first flip along the original execution path that makes the program halt,
+/// in time linear in the program length.
+pub fn repair(instructions: &[Op]) -> Option<Repair> {
+    let len = instructions.len() as Ip;
+
+    // instructions whose unmodified execution reaches the end, found by walking
+    // backwards from the end over the reversed control flow graph
+    let mut predecessors = vec![Vec::new(); instructions.len() + 1];
+    for (ip, &op) in instructions.iter().enumerate() {
+        let next = successor(op, ip as Ip);
+        if (0..=len).contains(&next) {
+            predecessors[next as usize].push(ip);
         }
-        visit_order.push(ip);
-
-        let instr = instructions[ip as usize];
-        let instr = if inverted == Some(ip) {
-            instr.invert()
-        } else {
-            instr
-        };
-        match instr {
-            Op::Acc(v) => acc += v,
-            Op::Jmp(offset) => {
-                ip += offset;
-                continue;
+    }
+    let mut halts = vec![false; instructions.len() + 1];
+    halts[instructions.len()] = true;
+    let mut to_visit = vec![instructions.len()];
+    while let Some(ip) = to_visit.pop() {
+        for &pred in &predecessors[ip] {
+            if !std::mem::replace(&mut halts[pred], true) {
+                to_visit.push(pred);
             }
-            Op::Nop(_) => {}
         }
-        ip += 1
     }
 
-    Ok(acc)
-}
-
-#[aoc(day8, part1)]
-pub fn day8_part1(instructions: &[Op]) -> i32 {
-    execute(instructions, on_visited_nop, None).unwrap_err()
+    let mut machine = Machine::new(instructions).with_loop_detection().traced();
+    machine.run();
+    let ip = machine.trace()?.iter().map(|entry| entry.ip).find(|&ip| {
+        let op = instructions[ip as usize];
+        let next = successor(op.invert(), ip);
+        op.invert() != op && (0..=len).contains(&next) && halts[next as usize]
+    })?;
+
+    let original = instructions[ip as usize];
+    let mut patched = instructions.to_owned();
+    patched[ip as usize] = original.invert();
+    let mut machine = Machine::new(&patched).with_loop_detection();
+    assert_eq!(machine.run(), Termination::Halted);
+
+    Some(Repair {
+        ip,
+        original,
+        replacement: original.invert(),
+        acc: machine.registers.acc,
+    })
 }
 
 #[aoc(day8, part2)]
 pub fn day8_part2(instructions: &[Op]) -> i32 {
-    fn on_visited(
-        instructions: &[Op],
-        _: Ip,
-        _: Acc,
-        _: Vec<bool>,
-        visit_order: Vec<Ip>,
-    ) -> Option<ExecResult> {
-        for visited in visit_order {
-            if let Op::Jmp(_) | Op::Nop(_) = instructions[visited as usize] {
-                if let result @ Ok(_) = execute(instructions, on_visited_nop, Some(visited)) {
-                    return Some(result);
-                }
-            }
-        }
-
-        panic!("couldn't find solution")
-    }
-    execute(instructions, on_visited, None).unwrap()
+    repair(instructions).expect("couldn't find solution").acc
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Op;
+    use super::{Ip, Machine, Op, Repair, Termination};
+    use aoc_utils::rng::XorShift;
 
     const EXAMPLE_INPUT: &str = r"nop +0
 acc +1
@@ -158,4 +129,93 @@ acc +6";
     fn day8_part2() {
         assert_eq!(super::day8_part2(&get_example_data()), 8);
     }
+
+    #[test]
+    fn repair() {
+        assert_eq!(
+            super::repair(&get_example_data()),
+            Some(Repair {
+                ip: 7,
+                original: Op::Jmp(-4),
+                replacement: Op::Nop(-4),
+                acc: 8,
+            })
+        );
+    }
+
+    fn repair_brute_force(instructions: &[Op]) -> Option<Repair> {
+        let mut machine = Machine::new(instructions).with_loop_detection().traced();
+        machine.run();
+
+        let mut patched = instructions.to_owned();
+        for entry in machine.trace().unwrap() {
+            let original = instructions[entry.ip as usize];
+            if let Op::Jmp(_) | Op::Nop(_) = original {
+                patched[entry.ip as usize] = original.invert();
+                let mut machine = Machine::new(&patched).with_loop_detection();
+                if machine.run() == Termination::Halted {
+                    return Some(Repair {
+                        ip: entry.ip,
+                        original,
+                        replacement: original.invert(),
+                        acc: machine.registers.acc,
+                    });
+                }
+                patched[entry.ip as usize] = original;
+            }
+        }
+
+        None
+    }
+
+    /// Program visiting every instruction in random order before halting,
+    /// with one random `jmp`/`nop` flipped so that it loops instead.
+    fn generate_corrupted(rng: &mut XorShift, len: usize) -> Vec<Op> {
+        let mut order: Vec<usize> = (0..len).collect();
+        for i in (1..len).rev() {
+            order.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+        order.push(len);
+
+        let mut program = vec![Op::Nop(0); len];
+        for pair in order.windows(2) {
+            let (ip, next) = (pair[0], pair[1]);
+            program[ip] = if next == ip + 1 {
+                match rng.below(2) {
+                    0 => Op::Acc(rng.below(100) as i32 - 50),
+                    _ => Op::Nop(rng.below(2 * len as u64) as Ip - len as Ip),
+                }
+            } else {
+                Op::Jmp(next as Ip - ip as Ip)
+            };
+        }
+
+        // corrupt the second half of the path so the loop is found late
+        loop {
+            let ip = order[len / 2 + rng.below((len - len / 2) as u64) as usize];
+            if let Op::Acc(_) = program[ip] {
+                continue;
+            }
+            let mut corrupted = program.clone();
+            corrupted[ip] = program[ip].invert();
+            if let Termination::InfiniteLoop { .. } =
+                Machine::new(&corrupted).with_loop_detection().run()
+            {
+                return corrupted;
+            }
+        }
+    }
+
+    #[test]
+    fn repair_large() {
+        let mut rng = XorShift(0x2020_0808);
+        for len in [10, 100, 1000, 3000] {
+            for _ in 0..5 {
+                let program = generate_corrupted(&mut rng, len);
+                let repair = super::repair(&program);
+                assert!(repair.is_some());
+                assert_eq!(repair, repair_brute_force(&program));
+            }
+        }
+    }
 }
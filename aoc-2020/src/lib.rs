@@ -4,6 +4,8 @@ use aoc_utils::libs::{
     aoc_runner_derive::{aoc, aoc_generator},
 };
 
+pub mod console;
+
 pub mod day1;
 pub mod day2;
 pub mod day3;
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 use aoc_runner_derive::{aoc, aoc_generator};
 
 use aoc_utils::lexical_parse_lines;
@@ -8,14 +10,65 @@ pub fn generate(s: &str) -> Vec<i32> {
     lexical_parse_lines(s).expect("couldn't parse input")
 }
 
+/// Yields the starting position of every window whose sum is larger than the sum of the
+/// window starting one position earlier. Only the last `width` values are kept in memory.
+pub struct WindowIncreases<I: Iterator> {
+    iter: I,
+    window: VecDeque<I::Item>,
+    width: usize,
+    position: usize,
+}
+
+impl<I> Iterator for WindowIncreases<I>
+where
+    I: Iterator,
+    I::Item: PartialOrd,
+{
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for value in self.iter.by_ref() {
+            if self.window.len() < self.width {
+                self.window.push_back(value);
+                continue;
+            }
+
+            // consecutive windows share all values except the first and the last one
+            let first = self.window.pop_front().unwrap();
+            let increased = value > first;
+            self.window.push_back(value);
+            self.position += 1;
+            if increased {
+                return Some(self.position);
+            }
+        }
+
+        None
+    }
+}
+
+pub fn window_increases<I>(values: I, width: usize) -> WindowIncreases<I::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: PartialOrd,
+{
+    assert!(width > 0, "window must not be empty");
+    WindowIncreases {
+        iter: values.into_iter(),
+        window: VecDeque::with_capacity(width),
+        width,
+        position: 0,
+    }
+}
+
 #[aoc(day1, part1)]
 pub fn day1_part1(values: &[i32]) -> i32 {
-    values.windows(2).filter(|s| s[0] < s[1]).count() as _
+    window_increases(values, 1).count() as _
 }
 
 #[aoc(day1, part2)]
 pub fn day1_part2(values: &[i32]) -> i32 {
-    values.windows(4).filter(|s| s[0] < s[3]).count() as _
+    window_increases(values, 3).count() as _
 }
 
 #[cfg(test)]
@@ -31,4 +84,44 @@ mod tests {
     fn day1_part2() {
         assert_eq!(super::day1_part2(EXAMPLE_DATA), 5);
     }
+
+    #[test]
+    fn positions() {
+        assert_eq!(
+            super::window_increases(EXAMPLE_DATA, 1).collect::<Vec<_>>(),
+            [1, 2, 3, 5, 6, 7, 9]
+        );
+        assert_eq!(
+            super::window_increases(EXAMPLE_DATA, 3).collect::<Vec<_>>(),
+            [1, 4, 5, 6, 7]
+        );
+        assert_eq!(super::window_increases(EXAMPLE_DATA, 10).count(), 0);
+        assert_eq!(super::window_increases(EXAMPLE_DATA, 11).count(), 0);
+    }
+
+    #[test]
+    fn matches_windows() {
+        let values: Vec<i64> = (0..1000_i64).map(|i| (i * 7919) % 263 - i / 3).collect();
+        for width in 1..=20 {
+            let expected: Vec<_> = values
+                .windows(width)
+                .map(|w| w.iter().sum::<i64>())
+                .collect::<Vec<_>>()
+                .windows(2)
+                .enumerate()
+                .filter(|(_, s)| s[0] < s[1])
+                .map(|(i, _)| i + 1)
+                .collect();
+            assert_eq!(
+                super::window_increases(values.iter(), width).collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let depths = (0..1_000_000_u64).map(|i| i % 1000);
+        assert_eq!(super::window_increases(depths, 500).count(), 500_000);
+    }
 }
@@ -17,88 +17,148 @@ pub fn generate<'input>(s: &'input str) -> Vec<Data<'input>> {
     s.lines().collect()
 }
 
-fn invalid_score(c: u8) -> i64 {
-    match c {
-        b')' => 3,
-        b']' => 57,
-        b'}' => 1197,
-        b'>' => 25137,
-        _ => unreachable!(),
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BracketPair {
+    pub open: char,
+    pub close: char,
+    /// score of a line corrupted by finding this closer
+    pub corrupted_score: u64,
+    /// score of this closer as part of a completion
+    pub completion_score: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Checked {
+    Ok,
+    /// `expected` is `None` when a closer has nothing to close
+    Corrupted {
+        column: usize,
+        expected: Option<char>,
+        found: char,
+    },
+    Incomplete {
+        completion: String,
+    },
 }
 
-fn completion_score(c: u8) -> i64 {
-    match c {
-        b')' => 1,
-        b']' => 2,
-        b'}' => 3,
-        b'>' => 4,
-        _ => unreachable!(),
+/// Bracket pairs to check, characters outside of them are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syntax {
+    pairs: Vec<BracketPair>,
+    /// completion scores are read as digits in this base
+    completion_base: u64,
+}
+
+impl Syntax {
+    pub fn new(pairs: Vec<BracketPair>, completion_base: u64) -> Self {
+        Syntax {
+            pairs,
+            completion_base,
+        }
+    }
+
+    pub fn navigation() -> Self {
+        let pair = |open, close, corrupted_score, completion_score| BracketPair {
+            open,
+            close,
+            corrupted_score,
+            completion_score,
+        };
+        Syntax::new(
+            vec![
+                pair('(', ')', 3, 1),
+                pair('[', ']', 57, 2),
+                pair('{', '}', 1197, 3),
+                pair('<', '>', 25137, 4),
+            ],
+            5,
+        )
+    }
+
+    fn closing(&self, c: char) -> Option<&BracketPair> {
+        self.pairs.iter().find(|pair| pair.close == c)
+    }
+
+    pub fn check(&self, line: &str) -> Checked {
+        let mut stack = vec![];
+        for (column, c) in line.chars().enumerate() {
+            if let Some(pair) = self.pairs.iter().find(|pair| pair.open == c) {
+                stack.push(pair.close);
+            } else if self.closing(c).is_some() {
+                match stack.pop() {
+                    Some(expected) if expected == c => {}
+                    expected => {
+                        return Checked::Corrupted {
+                            column,
+                            expected,
+                            found: c,
+                        }
+                    }
+                }
+            }
+        }
+
+        if stack.is_empty() {
+            Checked::Ok
+        } else {
+            Checked::Incomplete {
+                completion: stack.iter().rev().collect(),
+            }
+        }
+    }
+
+    /// `None` if a completion is too long for its score to fit in a `u64`.
+    pub fn score(&self, checked: &Checked) -> Option<u64> {
+        match checked {
+            Checked::Ok => Some(0),
+            Checked::Corrupted { found, .. } => Some(self.closing(*found)?.corrupted_score),
+            Checked::Incomplete { completion } => completion.chars().try_fold(0_u64, |acc, c| {
+                acc.checked_mul(self.completion_base)?
+                    .checked_add(self.closing(c)?.completion_score)
+            }),
+        }
     }
 }
 
 #[aoc(day10, part1)]
-pub fn day10_part1(data: &[Data<'_>]) -> i64 {
-    let mut stack = Vec::with_capacity(data[0].len());
+pub fn day10_part1(data: &[Data<'_>]) -> u64 {
+    let syntax = Syntax::navigation();
     data.iter()
-        .map(|data| {
-            stack.clear();
-            for c in data.bytes() {
-                match c {
-                    b'[' => stack.push(b']'),
-                    b'(' => stack.push(b')'),
-                    b'{' => stack.push(b'}'),
-                    b'<' => stack.push(b'>'),
-                    c => match stack.pop() {
-                        Some(end) if c == end => {}
-                        Some(_) => return invalid_score(c),
-                        None => unreachable!(),
-                    },
-                }
-            }
-            0
-        })
-        .sum()
+        .map(|line| syntax.check(line))
+        .filter(|checked| matches!(checked, Checked::Corrupted { .. }))
+        .map(|checked| syntax.score(&checked))
+        .sum::<Option<u64>>()
+        .expect("score overflow")
 }
 
-#[aoc(day10, part2)]
-pub fn day10_part2(data: &[Data<'_>]) -> i64 {
-    let mut stack = Vec::with_capacity(data[0].len());
-    let mut completion_scores = data
-        .iter()
-        .filter_map(|data| {
-            stack.clear();
-            for c in data.bytes() {
-                match c {
-                    b'[' => stack.push(b']'),
-                    b'(' => stack.push(b')'),
-                    b'{' => stack.push(b'}'),
-                    b'<' => stack.push(b'>'),
-                    c => match stack.pop() {
-                        Some(end) if c == end => {}
-                        Some(_) => return None,
-                        None => unreachable!(),
-                    },
-                }
+/// Median score of the completions of incomplete lines.
+pub fn middle_completion_score(syntax: &Syntax, data: &[Data<'_>]) -> anyhow::Result<u64> {
+    let mut completion_scores = Vec::new();
+    for line in data {
+        let checked = syntax.check(line);
+        if let Checked::Incomplete { completion } = &checked {
+            match syntax.score(&checked) {
+                Some(score) => completion_scores.push(score),
+                None => bail!("completion score of {:?} overflows", completion),
             }
-            Some(
-                stack
-                    .iter()
-                    .rev()
-                    .fold(0, |acc, &end| acc * 5 + completion_score(end)),
-            )
-        })
-        .collect_vec();
+        }
+    }
+    if completion_scores.is_empty() {
+        bail!("no incomplete lines");
+    }
 
     let center = completion_scores.len() / 2;
-    // println!("{}/2 = {}", completion_scores.len(), center);
-    // println!("{:?}", completion_scores);
-    *completion_scores.select_nth_unstable(center).1
+    Ok(*completion_scores.select_nth_unstable(center).1)
+}
+
+#[aoc(day10, part2)]
+pub fn day10_part2(data: &[Data<'_>]) -> u64 {
+    middle_completion_score(&Syntax::navigation(), data).expect("couldn't score completions")
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{BracketPair, Checked, Data, Syntax};
 
     const EXAMPLE_INPUT: &str = r#"[({(<(())[]>[[{[]{<()<>>
 [(()[<>])]({[<{<<[]>>(
@@ -140,4 +200,99 @@ mod tests {
     fn day10_part2() {
         assert_eq!(super::day10_part2(&get_example_data()), 288957);
     }
+
+    #[test]
+    fn check() {
+        let syntax = Syntax::navigation();
+        let data = get_example_data();
+        assert_eq!(
+            syntax.check(data[2]),
+            Checked::Corrupted {
+                column: 12,
+                expected: Some(']'),
+                found: '}'
+            }
+        );
+        let checked = syntax.check(data[0]);
+        assert_eq!(
+            checked,
+            Checked::Incomplete {
+                completion: "}}]])})]".to_owned()
+            }
+        );
+        assert_eq!(syntax.score(&checked), Some(288957));
+        assert_eq!(syntax.check("{()<>}"), Checked::Ok);
+    }
+
+    #[test]
+    fn unbalanced_closers() {
+        let syntax = Syntax::navigation();
+        assert_eq!(
+            syntax.check("()]"),
+            Checked::Corrupted {
+                column: 2,
+                expected: None,
+                found: ']'
+            }
+        );
+        assert_eq!(super::day10_part1(&["<>)", "(]"]), 3 + 57);
+    }
+
+    #[test]
+    fn custom_pairs() {
+        let syntax = Syntax::new(
+            vec![
+                BracketPair {
+                    open: '«',
+                    close: '»',
+                    corrupted_score: 7,
+                    completion_score: 1,
+                },
+                BracketPair {
+                    open: '(',
+                    close: ')',
+                    corrupted_score: 11,
+                    completion_score: 2,
+                },
+            ],
+            10,
+        );
+        assert_eq!(syntax.check("«a(b)c»"), Checked::Ok);
+        assert_eq!(
+            syntax.check("«x<(»"),
+            Checked::Corrupted {
+                column: 4,
+                expected: Some(')'),
+                found: '»'
+            }
+        );
+        let checked = syntax.check("««(");
+        assert_eq!(syntax.score(&checked), Some(211));
+    }
+
+    #[test]
+    fn long_completions() {
+        let syntax = Syntax::navigation();
+        // (5^28 - 1) / 4 still fits, one more closer doesn't
+        let fits = "(".repeat(28);
+        assert_eq!(
+            syntax.score(&syntax.check(&fits)),
+            Some((5_u64.pow(27) - 1) / 4 * 5 + 1)
+        );
+        let overflows = "(".repeat(29);
+        assert_eq!(syntax.score(&syntax.check(&overflows)), None);
+
+        assert_eq!(
+            super::middle_completion_score(&syntax, &[&fits, "[", "{"]).unwrap(),
+            3
+        );
+        assert!(super::middle_completion_score(&syntax, &[&fits, &overflows]).is_err());
+    }
+
+    #[test]
+    fn no_incomplete_lines() {
+        let syntax = Syntax::navigation();
+        assert!(super::middle_completion_score(&syntax, &[]).is_err());
+        assert!(super::middle_completion_score(&syntax, &["()", "(]"]).is_err());
+    }
 }
@@ -1,14 +1,15 @@
 #![allow(unused_imports)]
 
-use std::collections::VecDeque;
 use std::convert::TryFrom;
+use std::ops::ControlFlow;
 
 use anyhow::bail;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 
-use aoc_utils::grids::{for_neighbours_8, position_in_grid, print_digit, print_grid};
+use aoc_utils::automaton::{Boundary, CascadeRule, GridAutomaton, Neighbourhood};
+use aoc_utils::grids::Grid;
 use aoc_utils::libs::*;
 use aoc_utils::try_from_lines;
 
@@ -21,65 +22,52 @@ pub fn generate(s: &str) -> Vec<Data> {
         .collect()
 }
 
-fn simulate_flashes(data: &[Data], stop_after_synchronize: bool) -> i32 {
-    let mut data = data.to_owned();
-    let wh = (data[0].len(), data.len());
+/// Octopuses charge every step, flashing above 9 and charging their neighbours.
+struct Flash;
 
-    let mut queue = VecDeque::with_capacity(32);
-    let mut flashes = 0;
-
-    let mut iter = 0;
-    while stop_after_synchronize || iter < 100 {
-        #[cfg(debug_assertions)]
-        if iter % 10 == 0 {
-            println!("after {}", iter);
-            print_grid(&data, print_digit);
-            println!();
-        }
-        // increment all by 1
-        data.iter_mut().flatten().for_each(|d| match d {
-            d @ 0..=9 => *d += 1,
-            _ => unreachable!(),
-        });
-
-        // flash, charge adjacent, flash etc.
-        assert!(queue.is_empty());
-        while let Some(xy) = position_in_grid(&data, |&d| d == 10) {
-            queue.push_back(xy);
-            while let Some((x, y)) = queue.pop_front() {
-                match &mut data[y][x] {
-                    power @ 10 => {
-                        for_neighbours_8((x, y), wh, |pos| queue.push_back(pos));
-                        flashes += 1;
-                        *power = 0;
-                    }
-                    0 => {}
-                    power @ 1..=9 => *power += 1,
-                    _ => unreachable!(),
-                }
-            }
-        }
+impl CascadeRule<u8> for Flash {
+    fn charge(&self, energy: &mut u8) -> bool {
+        *energy += 1;
+        *energy > 9
+    }
 
-        iter += 1;
-        if stop_after_synchronize && data.iter().flatten().all(|&d| d == 0) {
-            return iter;
-        }
+    fn propagate(&self, energy: &mut u8) -> bool {
+        self.charge(energy)
     }
 
-    if stop_after_synchronize {
-        unreachable!("flashes didn't synchronize!");
+    fn settle(&self, energy: &mut u8) {
+        *energy = 0;
     }
-    flashes
+}
+
+fn octopuses(data: &[Data]) -> GridAutomaton<u8> {
+    let grid = Grid::from_rows(data.iter().map(|row| row.iter().copied()));
+    GridAutomaton::new(grid, Neighbourhood::Moore, Boundary::Bounded)
 }
 
 #[aoc(day11, part1)]
-pub fn day11_part1(data: &[Data]) -> i32 {
-    simulate_flashes(data, false)
+pub fn day11_part1(data: &[Data]) -> usize {
+    let mut flashes = 0;
+    octopuses(data).run_cascade(&Flash, |octopuses, flashed| {
+        flashes += flashed.len();
+        match octopuses.generation() {
+            100 => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        }
+    });
+
+    flashes
 }
 
 #[aoc(day11, part2)]
-pub fn day11_part2(data: &[Data]) -> i32 {
-    simulate_flashes(data, true)
+pub fn day11_part2(data: &[Data]) -> u64 {
+    octopuses(data).run_cascade(&Flash, |octopuses, flashed| {
+        if flashed.len() == octopuses.grid().len() {
+            ControlFlow::Break(octopuses.generation())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })
 }
 
 #[cfg(test)]
@@ -126,4 +114,13 @@ mod tests {
     fn day11_part2() {
         assert_eq!(super::day11_part2(&get_example_data()), 195);
     }
+
+    #[test]
+    fn small_example() {
+        let data = super::generate("11111\n19991\n19191\n19991\n11111");
+        let mut octopuses = super::octopuses(&data);
+        assert_eq!(octopuses.step_cascade(&super::Flash).len(), 9);
+        let expected = super::octopuses(&super::generate("34543\n40004\n50005\n40004\n34543"));
+        assert_eq!(octopuses.grid(), expected.grid());
+    }
 }
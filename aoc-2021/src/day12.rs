@@ -16,10 +16,51 @@ type Idx = i32;
 pub struct Data<'a> {
     adjacent: HashMap<Idx, Vec<Idx>>,
     interned: HashMap<Idx, &'a str>,
+    /// bit assigned to each small cave other than start and end
+    small: HashMap<Idx, u32>,
     start_idx: Idx,
     end_idx: Idx,
 }
 
+/// Set of visited small caves, used as part of the memoisation key in [`count_paths`].
+trait SmallSet: Clone + Eq + std::hash::Hash {
+    fn empty(len: usize) -> Self;
+    fn contains(&self, bit: u32) -> bool;
+    fn with(&self, bit: u32) -> Self;
+}
+
+impl SmallSet for u128 {
+    fn empty(len: usize) -> Self {
+        assert!(len <= u128::BITS as usize);
+        0
+    }
+
+    fn contains(&self, bit: u32) -> bool {
+        self & (1 << bit) != 0
+    }
+
+    fn with(&self, bit: u32) -> Self {
+        self | (1 << bit)
+    }
+}
+
+/// For inputs with more small caves than fit in a `u128`.
+impl SmallSet for Box<[u64]> {
+    fn empty(len: usize) -> Self {
+        vec![0; len.div_ceil(64)].into_boxed_slice()
+    }
+
+    fn contains(&self, bit: u32) -> bool {
+        self[bit as usize / 64] & (1 << (bit % 64)) != 0
+    }
+
+    fn with(&self, bit: u32) -> Self {
+        let mut set = self.clone();
+        set[bit as usize / 64] |= 1 << (bit % 64);
+        set
+    }
+}
+
 #[aoc_generator(day12)]
 pub fn generate<'input>(s: &'input str) -> Data<'input> {
     let mut data = Data::default();
@@ -44,71 +85,173 @@ pub fn generate<'input>(s: &'input str) -> Data<'input> {
     data.start_idx = inverse_interned["start"];
     data.end_idx = inverse_interned["end"];
 
+    for (&idx, name) in data.interned.iter().sorted() {
+        if idx != data.start_idx && idx != data.end_idx && !name.as_bytes()[0].is_ascii_uppercase()
+        {
+            let bit = data.small.len() as u32;
+            data.small.insert(idx, bit);
+        }
+    }
     data
 }
 
-fn count_paths(data: &Data<'_>, allow_small_revisit: bool) -> i32 {
-    let mut to_visit = Vec::with_capacity(data.adjacent.len());
-    let mut visited = Vec::with_capacity(data.adjacent.len());
-    to_visit.push((data.start_idx, 1, !allow_small_revisit));
+/// Iterator over all paths from `start` to `end`, in adjacency order.
+///
+/// Each small cave may be visited once, except for `revisit_budget` additional visits
+/// shared between all small caves.
+pub struct Paths<'d, 'a> {
+    data: &'d Data<'a>,
+    /// current path with the index of the next neighbour to try from each cave
+    stack: Vec<(Idx, usize)>,
+    visits: HashMap<Idx, u32>,
+    revisit_budget: u32,
+}
 
-    let mut paths_to_end = 0;
+impl<'d, 'a> Paths<'d, 'a> {
+    fn enter(&mut self, cave: Idx) {
+        if cave == self.data.start_idx {
+            return;
+        }
+        if self.data.small.contains_key(&cave) {
+            let visits = self.visits.entry(cave).or_default();
+            if *visits > 0 {
+                if self.revisit_budget == 0 {
+                    return;
+                }
+                self.revisit_budget -= 1;
+            }
+            *visits += 1;
+        }
+        self.stack.push((cave, 0));
+    }
 
-    while let Some((current, depth_then, small_revisited)) = to_visit.pop() {
-        visited.truncate(depth_then);
-        if current == data.end_idx {
-            paths_to_end += 1;
-            #[cfg(debug_assertions)]
-            {
-                println!(
-                    "{}",
-                    visited
-                        .iter()
-                        .map(|v| data.interned[v])
-                        .chain(["end"])
-                        .join(",")
-                );
+    fn leave(&mut self) {
+        if let Some((cave, _)) = self.stack.pop() {
+            if let Some(visits) = self.visits.get_mut(&cave) {
+                *visits -= 1;
+                if *visits > 0 {
+                    self.revisit_budget += 1;
+                }
             }
-            continue;
         }
+    }
+}
+
+impl<'d, 'a> Iterator for Paths<'d, 'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (cave, next_adjacent) = self.stack.last_mut()?;
+            let cave = *cave;
+
+            if cave == self.data.end_idx {
+                let path = self
+                    .stack
+                    .iter()
+                    .map(|(v, _)| self.data.interned[v])
+                    .collect();
+                self.leave();
+                return Some(path);
+            }
 
-        visited.push(current);
-        let depth = visited.len();
-
-        to_visit.extend(data.adjacent[&current].iter().rev().filter_map(|&adj| {
-            if data.interned[&adj].as_bytes()[0].is_ascii_uppercase() {
-                Some((adj, depth, small_revisited))
-            } else if adj == data.start_idx {
-                None
-            } else {
-                let visits = visited.iter().filter(|&&vis| vis == adj).count();
-
-                match (small_revisited, visits) {
-                    (false, 1) if allow_small_revisit => Some((adj, depth, true)),
-                    (_, 0) => Some((adj, depth, small_revisited)),
-                    (true, _) => None,
-                    _ => unreachable!(),
+            match self.data.adjacent[&cave].get(*next_adjacent) {
+                Some(&adj) => {
+                    *next_adjacent += 1;
+                    self.enter(adj);
                 }
+                None => self.leave(),
             }
-        }));
+        }
     }
+}
+
+pub fn paths<'d, 'a>(data: &'d Data<'a>, revisit_budget: u32) -> Paths<'d, 'a> {
+    Paths {
+        data,
+        stack: vec![(data.start_idx, 0)],
+        visits: HashMap::default(),
+        revisit_budget,
+    }
+}
+
+/// Counts the paths yielded by [`paths`] without enumerating them.
+pub fn count_paths(data: &Data<'_>, revisit_budget: u32) -> u64 {
+    fn count<S: SmallSet>(
+        data: &Data<'_>,
+        memo: &mut HashMap<(Idx, S, u32), u64>,
+        current: Idx,
+        visited: S,
+        revisit_budget: u32,
+    ) -> u64 {
+        if current == data.end_idx {
+            return 1;
+        }
+        let key = (current, visited, revisit_budget);
+        if let Some(&paths) = memo.get(&key) {
+            return paths;
+        }
+        let (_, visited, _) = &key;
 
-    paths_to_end
+        let mut paths = 0;
+        for &adj in &data.adjacent[&current] {
+            if adj == data.start_idx {
+                continue;
+            }
+            paths += match data.small.get(&adj) {
+                None => count(data, memo, adj, visited.clone(), revisit_budget),
+                Some(&bit) if !visited.contains(bit) => {
+                    count(data, memo, adj, visited.with(bit), revisit_budget)
+                }
+                Some(_) if revisit_budget > 0 => {
+                    count(data, memo, adj, visited.clone(), revisit_budget - 1)
+                }
+                Some(_) => 0,
+            };
+        }
+
+        memo.insert(key, paths);
+        paths
+    }
+
+    // the memo key stays a plain integer unless the input needs more bits
+    let small = data.small.len();
+    if small <= u128::BITS as usize {
+        let visited = u128::empty(small);
+        count(
+            data,
+            &mut HashMap::default(),
+            data.start_idx,
+            visited,
+            revisit_budget,
+        )
+    } else {
+        let visited = Box::<[u64]>::empty(small);
+        count(
+            data,
+            &mut HashMap::default(),
+            data.start_idx,
+            visited,
+            revisit_budget,
+        )
+    }
 }
 
 #[aoc(day12, part1)]
-pub fn day12_part1(data: &Data<'_>) -> i32 {
-    count_paths(data, false)
+pub fn day12_part1(data: &Data<'_>) -> u64 {
+    count_paths(data, 0)
 }
 
 #[aoc(day12, part2)]
-pub fn day12_part2(data: &Data<'_>) -> i32 {
-    count_paths(data, true)
+pub fn day12_part2(data: &Data<'_>) -> u64 {
+    count_paths(data, 1)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    use aoc_utils::libs::itertools::Itertools;
 
     use super::Data;
 
@@ -167,4 +310,66 @@ mod tests {
             assert_eq!(super::day12_part2(input), data);
         }
     }
+
+    #[test]
+    fn paths() {
+        let data = super::generate(EXAMPLE_INPUTS[0]);
+        let paths: HashSet<_> = super::paths(&data, 0).map(|path| path.join(",")).collect();
+        let expected: HashSet<_> = [
+            "start,A,b,A,c,A,end",
+            "start,A,b,A,end",
+            "start,A,b,end",
+            "start,A,c,A,b,A,end",
+            "start,A,c,A,b,end",
+            "start,A,c,A,end",
+            "start,A,end",
+            "start,b,A,c,A,end",
+            "start,b,A,end",
+            "start,b,end",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn count_matches_paths() {
+        for input in get_example_data().iter() {
+            for revisit_budget in 0..3 {
+                let paths = super::paths(input, revisit_budget).collect_vec();
+                assert_eq!(paths.iter().unique().count(), paths.len());
+                assert_eq!(
+                    super::count_paths(input, revisit_budget),
+                    paths.len() as u64
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn many_small_caves() {
+        let input = ["start-A", "A-end"]
+            .into_iter()
+            .map(String::from)
+            .chain((0..12).map(|i| format!("A-c{}", i)))
+            .join("\n");
+        let data = super::generate(&input);
+        // every sequence of distinct small caves: floor(e * 12!)
+        assert_eq!(super::count_paths(&data, 0), 1_302_061_345);
+    }
+
+    #[test]
+    fn more_small_caves_than_bits() {
+        // unreachable caves come first, so the reachable ones get bits above 128
+        let input = (0..200)
+            .map(|i| format!("x{}-y{}", i, i))
+            .chain(std::iter::once(EXAMPLE_INPUTS[0].to_string()))
+            .join("\n");
+        let data = super::generate(&input);
+        assert!(data.small.len() > 400);
+        assert_eq!(super::count_paths(&data, 0), 10);
+        assert_eq!(super::count_paths(&data, 1), 36);
+        assert_eq!(super::paths(&data, 1).count(), 36);
+    }
 }
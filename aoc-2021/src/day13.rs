@@ -1,13 +1,14 @@
 #![allow(unused_imports)]
 
+use std::cmp::Ordering;
 use std::convert::TryFrom;
-use std::detect::__is_feature_detected::xsavec;
 
 use anyhow::bail;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 
+use aoc_utils::grids::Grid;
 use aoc_utils::libs::*;
 use aoc_utils::try_from_lines;
 
@@ -34,6 +35,7 @@ pub fn generate(s: &str) -> Data {
         .map(|line| {
             let (_, line) = line.rsplit_once(' ').unwrap();
             let (axis, coord) = line.split_once('=').unwrap();
+            assert!(matches!(axis, "x" | "y"), "unknown fold axis {}", axis);
             (lexical::parse(coord).unwrap(), axis.as_bytes()[0])
         })
         .collect();
@@ -41,54 +43,125 @@ pub fn generate(s: &str) -> Data {
     Data { dots, folds }
 }
 
-fn fold(dots: &mut Vec<(u16, u16)>, folds: impl IntoIterator<Item = (u16, u8)>) {
-    for (fold, axis) in folds {
-        for (x, y) in dots.iter_mut() {
-            let coord = if axis == b'x' { x } else { y };
-            if *coord > fold {
-                let diff = *coord - fold;
-                *coord = fold - diff;
-            }
+/// Transparent sheet with dots, `width` by `height` in size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sheet {
+    width: usize,
+    height: usize,
+    /// sorted by row, then column
+    dots: Vec<(usize, usize)>,
+}
+
+impl Sheet {
+    /// Smallest sheet containing all the dots.
+    pub fn new(dots: &[(u16, u16)]) -> Self {
+        let dots = dots.iter().map(|&(x, y)| (x as usize, y as usize));
+        let width = dots.clone().map(|(x, _)| x + 1).max().unwrap_or(0);
+        let height = dots.clone().map(|(_, y)| y + 1).max().unwrap_or(0);
+        Self::from_dots(width, height, dots.collect())
+    }
+
+    fn from_dots(width: usize, height: usize, mut dots: Vec<(usize, usize)>) -> Self {
+        dots.sort_unstable_by_key(|&(x, y)| (y, x));
+        dots.dedup();
+        Sheet {
+            width,
+            height,
+            dots,
         }
     }
 
-    dots.sort_unstable_by_key(|&(x, y)| (y, x));
-    dots.dedup();
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn dots(&self) -> &[(usize, usize)] {
+        &self.dots
+    }
+
+    /// Folds the part after the line `axis = at` over the part before it. If the folded part
+    /// is the larger one, it sticks out past the original edge and becomes the new edge.
+    /// Dots on the fold line disappear.
+    pub fn fold(&self, (at, axis): (u16, u8)) -> Sheet {
+        let at = at as usize;
+        let size = match axis {
+            b'x' => self.width,
+            _ => self.height,
+        };
+        let new_size = at.max(size.saturating_sub(at + 1));
+        let reflect = |c: usize| match c.cmp(&at) {
+            Ordering::Less => Some(new_size - (at - c)),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(new_size - (c - at)),
+        };
+
+        let dots = self.dots.iter().filter_map(|&(x, y)| match axis {
+            b'x' => Some((reflect(x)?, y)),
+            _ => Some((x, reflect(y)?)),
+        });
+        match axis {
+            b'x' => Self::from_dots(new_size, self.height, dots.collect()),
+            _ => Self::from_dots(self.width, new_size, dots.collect()),
+        }
+    }
+
+    pub fn to_grid(&self) -> Grid<bool> {
+        let mut grid = Grid::from_cells(
+            self.width,
+            self.height,
+            vec![false; self.width * self.height],
+        );
+        for &dot in &self.dots {
+            grid[dot] = true;
+        }
+        grid
+    }
+
+    /// Rows of `#` for dots and `.` for empty spots, separated by newlines.
+    pub fn render(&self) -> String {
+        self.to_grid()
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|&dot| if dot { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+}
+
+/// The sheet after each of the folds.
+pub fn fold_all(data: &Data) -> Vec<Sheet> {
+    let mut sheet = Sheet::new(&data.dots);
+    data.folds
+        .iter()
+        .map(|&fold| {
+            sheet = sheet.fold(fold);
+            sheet.clone()
+        })
+        .collect()
 }
 
 #[aoc(day13, part1)]
-pub fn day13_part1(data: &Data) -> i32 {
-    let mut data = data.clone();
-    fold(&mut data.dots, data.folds[..1].iter().copied());
-    data.dots.len() as _
+pub fn day13_part1(data: &Data) -> usize {
+    Sheet::new(&data.dots).fold(data.folds[0]).dots().len()
 }
 
 #[aoc(day13, part2)]
 pub fn day13_part2(data: &Data) -> String {
-    let mut data = data.clone();
-    fold(&mut data.dots, data.folds);
-
-    let mut line = 0;
-    let mut column = 0;
-    for (x, y) in data.dots {
-        if y > line {
-            println!();
-            assert_eq!(line + 1, y);
-            line = y;
-            column = 0;
-        }
-
-        print!("{}\u{2588}", " ".repeat((x - column) as _));
-        column = x + 1;
-    }
-    println!();
-
-    unimplemented!()
+    let sheet = fold_all(data)
+        .pop()
+        .unwrap_or_else(|| Sheet::new(&data.dots));
+    sheet.render()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Data, Sheet};
 
     const EXAMPLE_INPUT: &str = r#"6,10
 0,14
@@ -149,8 +222,41 @@ fold along x=5"#;
     }
 
     #[test]
-    #[ignore]
     fn day13_part2() {
-        assert_eq!(super::day13_part2(&get_example_data()), "O".to_owned());
+        assert_eq!(
+            super::day13_part2(&get_example_data()),
+            "#####\n#...#\n#...#\n#...#\n#####\n.....\n....."
+        );
+    }
+
+    #[test]
+    fn intermediate_sheets() {
+        let sheets = super::fold_all(&get_example_data());
+        assert_eq!(sheets.len(), 2);
+        assert_eq!((sheets[0].width(), sheets[0].height()), (11, 7));
+        assert_eq!(
+            sheets[0].render(),
+            "#.##..#..#.\n\
+             #...#......\n\
+             ......#...#\n\
+             #...#......\n\
+             .#.#..#.###\n\
+             ...........\n\
+             ..........."
+        );
+        let grid = sheets[1].to_grid();
+        assert!(grid[(0, 0)] && !grid[(1, 1)]);
+    }
+
+    #[test]
+    fn asymmetric_folds() {
+        let sheet = Sheet::new(&[(0, 0), (10, 0), (3, 1), (5, 1)]);
+        let folded = sheet.fold((3, b'x'));
+        assert_eq!((folded.width(), folded.height()), (7, 2));
+        assert_eq!(folded.render(), "#...#..\n.....#.");
+
+        let folded = sheet.fold((1, b'y'));
+        assert_eq!((folded.width(), folded.height()), (11, 1));
+        assert_eq!(folded.dots(), [(0, 0), (10, 0)]);
     }
 }
@@ -1,6 +1,7 @@
 #![allow(unused_imports)]
 
 use std::convert::TryFrom;
+use std::ops::Sub;
 
 use anyhow::bail;
 use anyhow::Context;
@@ -8,111 +9,220 @@ use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 
 use aoc_utils::libs::*;
+use aoc_utils::linalg::{self, Matrix};
 use aoc_utils::*;
 
+/// Index of an element name in [`Data::elements`].
+pub type Element = usize;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Data {
-    polymer: [[u64; 26]; 26],
-    last: u8,
-    rules: Vec<((u8, u8), u8)>,
+    /// element names in order of first appearance
+    elements: Vec<char>,
+    template: Vec<Element>,
+    /// pairs and the sequences inserted between them
+    rules: Vec<((Element, Element), Vec<Element>)>,
 }
 
-#[aoc_generator(day14)]
-pub fn generate(s: &str) -> Data {
-    let (polymer, rules) = s.split_once("\n\n").unwrap();
+pub fn parse(s: &str) -> anyhow::Result<Data> {
+    let (template, rule_lines) = s.split_once("\n\n").context("missing rules")?;
 
-    let (polymer, last) = {
-        let mut polymer_table = [[0; 26]; 26];
-        for p in polymer.as_bytes().windows(2) {
-            polymer_table[(p[0] - b'A') as usize][(p[1] - b'A') as usize] += 1;
+    let mut elements = Vec::new();
+    let mut intern = |c: char| match elements.iter().position(|&e| e == c) {
+        Some(i) => i,
+        None => {
+            elements.push(c);
+            elements.len() - 1
         }
-        let last = *polymer.as_bytes().last().unwrap() - b'A';
-        (polymer_table, last)
     };
 
-    let rules = rules
-        .lines()
-        .map(|line| {
-            let (pair, insert) = line.split_once(" -> ").unwrap();
-            let pair = pair.as_bytes();
-            let [p1, p2, i] = [pair[0], pair[1], insert.as_bytes()[0]].map(|b| b - b'A');
-            ((p1, p2), i)
-        })
-        .collect();
-
-    Data {
-        polymer,
-        last,
-        rules,
+    let template = template.trim().chars().map(&mut intern).collect();
+    let mut rules: Vec<((Element, Element), Vec<Element>)> = Vec::new();
+    for line in rule_lines.lines() {
+        let (pair, insert) = line.split_once(" -> ").context("missing arrow")?;
+        let (p1, p2) = pair
+            .chars()
+            .collect_tuple()
+            .with_context(|| format!("rule for {:?} isn't for a pair", pair))?;
+        let pair = (intern(p1), intern(p2));
+        if rules.iter().any(|(p, _)| *p == pair) {
+            bail!("duplicate rule for {:?}", (p1, p2));
+        }
+        rules.push((pair, insert.trim().chars().map(&mut intern).collect()));
     }
+
+    Ok(Data {
+        elements,
+        template,
+        rules,
+    })
 }
 
-fn _print_freqs(data: &Data) {
-    for (i, p) in data.polymer.iter().enumerate() {
-        println!("{}: {:?}", (i as u8 + b'A') as char, p);
-    }
-    println!();
+#[aoc_generator(day14)]
+pub fn generate(s: &str) -> Data {
+    parse(s).expect("couldn't parse input")
 }
 
-fn polymerize(data: &Data, steps: usize) -> u64 {
-    let mut data = data.clone();
-
-    for _step in 0..steps {
-        let mut new_polymer = data.polymer.make_default();
-        for &((p1, p2), insert) in data.rules.iter() {
-            let [p1, p2, insert] = [p1, p2, insert].map(|l| l as usize);
-            new_polymer[p1][insert] += data.polymer[p1][p2];
-            new_polymer[insert][p2] += data.polymer[p1][p2]
+impl Data {
+    fn pair_index(&self, (p1, p2): (Element, Element)) -> usize {
+        p1 * self.elements.len() + p2
+    }
+
+    /// Pairs replacing each pair in a step, by [`Data::pair_index`]. Pairs without a rule
+    /// are left as they are.
+    fn expansions(&self) -> Vec<Vec<usize>> {
+        let n = self.elements.len();
+        let mut expansions = (0..n * n).map(|pair| vec![pair]).collect_vec();
+        for &((p1, p2), ref insert) in &self.rules {
+            expansions[self.pair_index((p1, p2))] = [p1]
+                .iter()
+                .chain(insert)
+                .chain([&p2])
+                .tuple_windows()
+                .map(|(&a, &b)| self.pair_index((a, b)))
+                .collect();
+        }
+        expansions
+    }
+
+    fn template_pairs(&self) -> impl Iterator<Item = usize> + '_ {
+        self.template
+            .iter()
+            .tuple_windows()
+            .map(|(&p1, &p2)| self.pair_index((p1, p2)))
+    }
+
+    /// Element counts, given the number of each pair in a polymer.
+    fn histogram_of<T: linalg::Element + PartialEq>(&self, pairs: &[T]) -> Vec<(char, T)> {
+        // every element but the last starts exactly one pair
+        let mut counts = vec![T::zero(); self.elements.len()];
+        if let Some(&last) = self.template.last() {
+            counts[last] = T::one();
+        }
+        for (pair, count) in pairs.iter().enumerate() {
+            let first = pair / self.elements.len();
+            counts[first] = counts[first].plus(count);
         }
-        data.polymer = new_polymer;
 
-        #[cfg(debug_assertions)]
-        {
-            println!("After step {}", _step + 1);
-            _print_freqs(&data);
+        self.elements
+            .iter()
+            .copied()
+            .zip(counts)
+            .filter(|(_, count)| *count != T::zero())
+            .sorted_by_key(|&(element, _)| element)
+            .collect()
+    }
+
+    /// Whether each pair is in the template or can be created by insertions.
+    fn reachable(&self, expansions: &[Vec<usize>]) -> Vec<bool> {
+        let mut seen = vec![false; expansions.len()];
+        let mut to_visit = self.template_pairs().collect_vec();
+        while let Some(pair) = to_visit.pop() {
+            if !std::mem::replace(&mut seen[pair], true) {
+                to_visit.extend(&expansions[pair]);
+            }
         }
+        seen
     }
 
-    #[cfg(debug_assertions)]
-    {
-        _print_freqs(&data);
+    /// Pairs without a rule which are in the template or can be created by insertions.
+    pub fn missing_rules(&self) -> Vec<(char, char)> {
+        let expansions = self.expansions();
+        let seen = self.reachable(&expansions);
 
-        let mut freq = data
-            .polymer
+        let n = self.elements.len();
+        let ruled = self
+            .rules
             .iter()
-            .enumerate()
-            .map(|(i, follow)| {
-                let follow = follow.iter().sum::<u64>();
-                if i == data.last as usize {
-                    ((i as u8 + b'A') as char, follow + 1)
-                } else {
-                    ((i as u8 + b'A') as char, follow)
-                }
-            })
+            .map(|&(pair, _)| self.pair_index(pair))
             .collect_vec();
+        (0..n * n)
+            .filter(|pair| seen[*pair] && !ruled.contains(pair))
+            .map(|pair| (self.elements[pair / n], self.elements[pair % n]))
+            .sorted()
+            .collect()
+    }
+}
 
-        freq.sort_by_key(|&(_, f)| f);
-        println!("{:?}", freq);
+/// Number of each element after `steps` insertion steps, sorted by element name.
+pub fn histogram(data: &Data, steps: usize) -> Vec<(char, u64)> {
+    let expansions = data.expansions();
+    let mut pairs = vec![0; expansions.len()];
+    for pair in data.template_pairs() {
+        pairs[pair] += 1;
     }
 
-    let (min, max) = data
-        .polymer
-        .iter()
-        .enumerate()
-        .map(|(i, follow)| {
-            let follow = follow.iter().sum::<u64>();
-            if i == data.last as usize {
-                follow + 1
-            } else {
-                follow
+    for _ in 0..steps {
+        let mut new_pairs = vec![0; pairs.len()];
+        for (pair, &count) in pairs.iter().enumerate() {
+            for &new_pair in &expansions[pair] {
+                new_pairs[new_pair] += count;
             }
-        })
-        .filter(|&f| f != 0)
+        }
+        pairs = new_pairs;
+    }
+
+    data.histogram_of(&pairs)
+}
+
+/// Same as [`histogram`], in `O(log steps)`.
+///
+/// The step matrix only covers pairs reachable from the template, which is usually far
+/// fewer than all pairs of elements, but it can still grow to `n⁴` entries for `n` elements.
+pub fn histogram_pow<T: linalg::Element + PartialEq>(data: &Data, steps: u64) -> Vec<(char, T)> {
+    let expansions = data.expansions();
+    let reachable = data.reachable(&expansions);
+    let pairs = (0..expansions.len())
+        .filter(|&pair| reachable[pair])
+        .collect_vec();
+    let mut compact = vec![usize::MAX; expansions.len()];
+    for (i, &pair) in pairs.iter().enumerate() {
+        compact[pair] = i;
+    }
+
+    let mut step = Matrix::<T>::zero(pairs.len());
+    for (i, &pair) in pairs.iter().enumerate() {
+        for &new_pair in &expansions[pair] {
+            let j = compact[new_pair];
+            step[(j, i)] = step[(j, i)].plus(&T::one());
+        }
+    }
+
+    let mut counts = vec![T::zero(); pairs.len()];
+    for pair in data.template_pairs() {
+        let i = compact[pair];
+        counts[i] = counts[i].plus(&T::one());
+    }
+
+    let mut all_pairs = vec![T::zero(); expansions.len()];
+    for (&pair, count) in pairs.iter().zip(step.pow(steps).apply(&counts)) {
+        all_pairs[pair] = count;
+    }
+    data.histogram_of(&all_pairs)
+}
+
+fn spread<T: Ord + Clone + Sub<Output = T>>(histogram: Vec<(char, T)>) -> T {
+    let (min, max) = histogram
+        .into_iter()
+        .map(|(_, count)| count)
         .minmax()
         .into_option()
         .unwrap();
 
-    (max - min) as _
+    max - min
+}
+
+/// Difference between the most and least common elements after `steps` steps.
+fn polymerize(data: &Data, steps: usize) -> u64 {
+    spread(histogram(data, steps))
+}
+
+/// Same as [`polymerize`], in `O(log steps)`.
+pub fn polymerize_pow<T>(data: &Data, steps: u64) -> T
+where
+    T: linalg::Element + Ord + Sub<Output = T>,
+{
+    spread(histogram_pow(data, steps))
 }
 
 #[aoc(day14, part1)]
@@ -127,7 +237,9 @@ pub fn day14_part2(data: &Data) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Data, Element};
+    use aoc_utils::libs::itertools::Itertools;
+    use aoc_utils::libs::num_bigint::BigUint;
 
     const EXAMPLE_INPUT: &str = r#"NNCB
 
@@ -149,38 +261,31 @@ CC -> N
 CN -> C"#;
 
     fn get_example_data() -> Data {
-        Data {
-            polymer: {
-                let mut polymer = Default::default();
+        const N: Element = 0;
+        const C: Element = 1;
+        const B: Element = 2;
+        const H: Element = 3;
 
-                if false {
-                    polymer
-                } else {
-                    polymer[(b'N' - b'A') as usize][(b'N' - b'A') as usize] = 1;
-                    polymer[(b'N' - b'A') as usize][(b'C' - b'A') as usize] = 1;
-                    polymer[(b'C' - b'A') as usize][(b'B' - b'A') as usize] = 1;
-
-                    polymer
-                }
-            },
-            last: b'B' - b'A',
+        Data {
+            elements: vec!['N', 'C', 'B', 'H'],
+            template: vec![N, N, C, B],
             rules: vec![
-                ((b'C' - b'A', b'H' - b'A'), b'B' - b'A'),
-                ((b'H' - b'A', b'H' - b'A'), b'N' - b'A'),
-                ((b'C' - b'A', b'B' - b'A'), b'H' - b'A'),
-                ((b'N' - b'A', b'H' - b'A'), b'C' - b'A'),
-                ((b'H' - b'A', b'B' - b'A'), b'C' - b'A'),
-                ((b'H' - b'A', b'C' - b'A'), b'B' - b'A'),
-                ((b'H' - b'A', b'N' - b'A'), b'C' - b'A'),
-                ((b'N' - b'A', b'N' - b'A'), b'C' - b'A'),
-                ((b'B' - b'A', b'H' - b'A'), b'H' - b'A'),
-                ((b'N' - b'A', b'C' - b'A'), b'B' - b'A'),
-                ((b'N' - b'A', b'B' - b'A'), b'B' - b'A'),
-                ((b'B' - b'A', b'N' - b'A'), b'B' - b'A'),
-                ((b'B' - b'A', b'B' - b'A'), b'N' - b'A'),
-                ((b'B' - b'A', b'C' - b'A'), b'B' - b'A'),
-                ((b'C' - b'A', b'C' - b'A'), b'N' - b'A'),
-                ((b'C' - b'A', b'N' - b'A'), b'C' - b'A'),
+                ((C, H), vec![B]),
+                ((H, H), vec![N]),
+                ((C, B), vec![H]),
+                ((N, H), vec![C]),
+                ((H, B), vec![C]),
+                ((H, C), vec![B]),
+                ((H, N), vec![C]),
+                ((N, N), vec![C]),
+                ((B, H), vec![H]),
+                ((N, C), vec![B]),
+                ((N, B), vec![B]),
+                ((B, N), vec![B]),
+                ((B, B), vec![N]),
+                ((B, C), vec![B]),
+                ((C, C), vec![N]),
+                ((C, N), vec![C]),
             ],
         }
     }
@@ -199,4 +304,120 @@ CN -> C"#;
     fn day14_part2() {
         assert_eq!(super::day14_part2(&get_example_data()), 2188189693529);
     }
+
+    #[test]
+    fn polymerize_pow() {
+        let data = get_example_data();
+        assert_eq!(super::polymerize_pow::<u64>(&data, 10), 1588);
+        assert_eq!(super::polymerize_pow::<u64>(&data, 40), 2188189693529);
+        assert_eq!(
+            super::polymerize_pow::<BigUint>(&data, 100),
+            BigUint::from(super::polymerize_pow::<u128>(&data, 100))
+        );
+        assert!(super::polymerize_pow::<BigUint>(&data, 1000) > BigUint::from(u128::MAX));
+    }
+
+    #[test]
+    fn histogram() {
+        let data = get_example_data();
+        let expected = [('B', 1749), ('C', 298), ('H', 161), ('N', 865)];
+        assert_eq!(super::histogram(&data, 10), expected);
+        assert_eq!(super::histogram_pow::<u64>(&data, 10), expected);
+        assert!(data.missing_rules().is_empty());
+    }
+
+    /// Inserts by rewriting the whole polymer.
+    fn expand(template: &str, rules: &[(&str, &str)], steps: usize) -> Vec<(char, u64)> {
+        let mut polymer = template.chars().collect::<Vec<_>>();
+        for _ in 0..steps {
+            let mut next = vec![polymer[0]];
+            for (a, b) in polymer.iter().zip(&polymer[1..]) {
+                let pair = [*a, *b].iter().collect::<String>();
+                if let Some((_, insert)) = rules.iter().find(|(p, _)| *p == pair) {
+                    next.extend(insert.chars());
+                }
+                next.push(*b);
+            }
+            polymer = next;
+        }
+
+        polymer
+            .into_iter()
+            .sorted()
+            .group_by(|&c| c)
+            .into_iter()
+            .map(|(c, group)| (c, group.count() as u64))
+            .collect()
+    }
+
+    #[test]
+    fn arbitrary_alphabets() {
+        let rules = [("aβ", "γa"), ("βa", ""), ("aγ", "ββ"), ("γa", "β")];
+        let input = format!(
+            "aβaγ\n\n{}",
+            rules
+                .iter()
+                .map(|(p, i)| format!("{} -> {}", p, i))
+                .join("\n")
+        );
+        let data = super::generate(&input);
+        for steps in 0..6 {
+            assert_eq!(
+                super::histogram(&data, steps),
+                expand("aβaγ", &rules, steps),
+                "{}",
+                steps
+            );
+        }
+        assert_eq!(data.missing_rules(), [('β', 'β'), ('β', 'γ'), ('γ', 'β')]);
+    }
+
+    #[test]
+    fn full_alphabet() {
+        // each neighbouring pair inserts a copy of its first letter
+        let letters = ('a'..='z').collect::<String>();
+        let rules = letters
+            .chars()
+            .tuple_windows()
+            .map(|(a, b)| (format!("{}{}", a, b), a.to_string()))
+            .collect_vec();
+        let input = format!(
+            "{}\n\n{}",
+            letters,
+            rules
+                .iter()
+                .map(|(p, i)| format!("{} -> {}", p, i))
+                .join("\n")
+        );
+        let data = super::generate(&input);
+        assert_eq!(data.elements.len(), 26);
+
+        let rules = rules
+            .iter()
+            .map(|(p, i)| (p.as_str(), i.as_str()))
+            .collect_vec();
+        for steps in 0..4 {
+            assert_eq!(
+                super::histogram_pow::<u64>(&data, steps),
+                expand(&letters, &rules, steps as usize)
+            );
+        }
+        assert_eq!(
+            super::histogram_pow::<u64>(&data, 1000),
+            super::histogram(&data, 1000)
+        );
+        // every step adds one more of each letter but `z`
+        let histogram = super::histogram_pow::<u64>(&data, 1_000_000_000_000);
+        assert!(histogram[..25]
+            .iter()
+            .all(|&(_, count)| count == 1_000_000_000_001));
+        assert_eq!(histogram[25], ('z', 1));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(super::parse("AB\n\nAB -> C\nAB -> D").is_err());
+        assert!(super::parse("AB\n\nABC -> D").is_err());
+        assert!(super::parse("AB").is_err());
+    }
 }
@@ -1,5 +1,6 @@
 #![allow(unused_imports)]
 
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::ops::RangeInclusive;
 
@@ -11,80 +12,170 @@ use itertools::Itertools;
 use aoc_utils::libs::*;
 use aoc_utils::{try_from_lines, Ext};
 
+pub type Coord = i64;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Data {
-    x: RangeInclusive<i32>,
-    y: RangeInclusive<i32>,
+    x: RangeInclusive<Coord>,
+    y: RangeInclusive<Coord>,
 }
 
 #[aoc_generator(day17)]
 pub fn generate(s: &str) -> Data {
-    let (_, coords) = s.split_once(": ").unwrap();
+    let (_, coords) = s.trim().split_once(": ").unwrap();
     let (x, y) = coords.split_once(", ").unwrap();
     let [x, y] = [x, y].map(|coord| {
         let (start, end) = coord[2..].split_once("..").unwrap();
-        let [start, end] = [start, end].map(|n| lexical::parse(n).unwrap());
-        start..=end
+        let [start, end]: [Coord; 2] = [start, end].map(|n| lexical::parse(n).unwrap());
+        start.min(end)..=start.max(end)
     });
 
     Data { x, y }
 }
 
+/// A launch velocity and every step after which the probe is inside the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub velocity: (Coord, Coord),
+    pub steps: Vec<u64>,
+}
+
+impl Hit {
+    /// Highest `y` position reached, counting the launch position.
+    pub fn peak(&self) -> Coord {
+        let vy = self.velocity.1.max(0);
+        vy * (vy + 1) / 2
+    }
+}
+
+/// `a / b` rounded down, for `b > 0`.
+fn floor_div(a: Coord, b: Coord) -> Coord {
+    a.div_euclid(b)
+}
+
+/// `a / b` rounded up, for `b > 0`.
+fn ceil_div(a: Coord, b: Coord) -> Coord {
+    -(-a).div_euclid(b)
+}
+
+fn triangle(n: Coord) -> Coord {
+    n * (n + 1) / 2
+}
+
+/// Smallest `v >= 0` with `triangle(v) >= t`.
+fn triangle_root(t: Coord) -> Coord {
+    // the float estimate is only a starting point
+    let mut v = ((2 * t.max(0)) as f64).sqrt() as Coord;
+    while triangle(v) < t {
+        v += 1;
+    }
+    while v > 0 && triangle(v - 1) >= t {
+        v -= 1;
+    }
+    v
+}
+
+/// Smallest `vx` putting the probe at `x >= t` after `n` steps.
+///
+/// The position after `n` steps is odd and strictly increasing in `vx`, so the
+/// velocities reaching a range after `n` steps form a range too.
+fn min_vx(n: Coord, t: Coord) -> Coord {
+    if t <= 0 {
+        return -max_vx(n, -t);
+    }
+
+    // the probe stops moving after `vx` steps
+    let stopped = triangle_root(t);
+    if stopped <= n {
+        stopped
+    } else {
+        ceil_div(t + n * (n - 1) / 2, n)
+    }
+}
+
+/// Largest `vx` putting the probe at `x <= t` after `n` steps.
+fn max_vx(n: Coord, t: Coord) -> Coord {
+    min_vx(n, t + 1) - 1
+}
+
+/// Velocities putting the probe in `x` after `n` steps.
+fn vx_range(n: Coord, x: &RangeInclusive<Coord>) -> RangeInclusive<Coord> {
+    min_vx(n, *x.start())..=max_vx(n, *x.end())
+}
+
+/// Velocities putting the probe in `y` after `n` steps.
+fn vy_range(n: Coord, y: &RangeInclusive<Coord>) -> RangeInclusive<Coord> {
+    let fallen = n * (n - 1) / 2;
+    ceil_div(y.start() + fallen, n)..=floor_div(y.end() + fallen, n)
+}
+
+/// Last step after which the probe can be inside the target, or `None` if some
+/// velocities hit it infinitely often.
+fn max_steps(data: &Data) -> Option<Coord> {
+    let (&x1, &x2) = (data.x.start(), data.x.end());
+    let (&y1, &y2) = (data.y.start(), data.y.end());
+
+    // a probe starting upwards crosses `y = 0` again falling at `vy + 1`
+    let y_bound = if y2 < 0 {
+        Some(-2 * y1)
+    } else if y1 > 0 {
+        Some(2 * y2)
+    } else {
+        None
+    };
+
+    // the probe moves at least one unit a step until it stops
+    let (near, far) = if x1 > 0 { (x1, x2) } else { (-x2, -x1) };
+    let stops_inside = near <= 0 || triangle(triangle_root(near)) <= far;
+    let x_bound = (!stops_inside).then_some(far);
+
+    match (x_bound, y_bound) {
+        (Some(x), Some(y)) => Some(x.min(y)),
+        (bound, None) | (None, bound) => bound,
+    }
+}
+
+/// Every velocity hitting the target, sorted by velocity.
+pub fn solve(data: &Data) -> anyhow::Result<Vec<Hit>> {
+    let max_steps = match max_steps(data) {
+        Some(max_steps) => max_steps,
+        None => bail!("infinitely many velocities hit {:?}", data),
+    };
+
+    let mut hits = BTreeMap::<_, Vec<_>>::new();
+    for n in 1..=max_steps {
+        let vys = vy_range(n, &data.y);
+        if vys.is_empty() {
+            continue;
+        }
+        for vx in vx_range(n, &data.x) {
+            for vy in vys.clone() {
+                hits.entry((vx, vy)).or_default().push(n as u64);
+            }
+        }
+    }
+
+    Ok(hits
+        .into_iter()
+        .map(|(velocity, steps)| Hit { velocity, steps })
+        .collect())
+}
+
 #[aoc(day17, part1)]
-pub fn day17_part1(data: &Data) -> i32 {
-    (-2000..0)
-        .find_map(|vy0| {
-            let mut vy = vy0;
-            std::iter::successors(Some(0), |y| {
-                vy -= 1;
-                Some(y + vy + 1)
-            })
-            .take_while(|y| y >= data.y.start())
-            .find(|y| y <= data.y.end())
-            .map(|_| (vy0.pow(2) + vy0) / 2)
-        })
-        .unwrap()
+pub fn day17_part1(data: &Data) -> Coord {
+    let hits = solve(data).expect("couldn't solve");
+    hits.iter().map(Hit::peak).max().unwrap()
 }
 
 #[aoc(day17, part2)]
-pub fn day17_part2(data: &Data) -> i32 {
-    let start = ((2 * data.x.start()) as f32).sqrt().floor() as i32;
-    (-500..500)
-        .flat_map(|vy0| (start..=data.x.end() + 1).map(move |vx0: i32| (vx0, vy0)))
-        .filter_map(|(vx0, vy0)| {
-            let (mut vx, mut vy) = (vx0, vy0);
-            std::iter::successors(Some((0, 0)), |(x, y)| {
-                let x = x + vx;
-                let y = y + vy;
-                vy -= 1;
-                vx -= vx.signum();
-
-                Some((x, y))
-            })
-            .take_while(|(x, y)| {
-                y >= data.y.start()
-                    && if data.x.start() < &0 {
-                        x >= data.x.start()
-                    } else {
-                        x <= data.x.end()
-                    }
-            })
-            .find(|(x, y)| {
-                y <= data.y.end()
-                    && if data.x.start() < &0 {
-                        x <= data.x.end()
-                    } else {
-                        x >= data.x.start()
-                    }
-            })
-            .map(|_| (vx0, vy0))
-        })
-        .count() as _
+pub fn day17_part2(data: &Data) -> usize {
+    solve(data).expect("couldn't solve").len()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Coord, Data, Hit};
+    use aoc_utils::rng::XorShift;
 
     const EXAMPLE_INPUT: &str = r#"target area: x=20..30, y=-10..-5"#;
 
@@ -109,4 +200,123 @@ mod tests {
     fn day17_part2() {
         assert_eq!(super::day17_part2(&get_example_data()), 112);
     }
+
+    /// Simulates every velocity which could reach the target.
+    fn brute_force(data: &Data) -> Vec<Hit> {
+        let bound = [data.x.start(), data.x.end(), data.y.start(), data.y.end()]
+            .iter()
+            .map(|c| c.abs())
+            .max()
+            .unwrap()
+            + 1;
+
+        let mut hits = Vec::new();
+        for vx0 in -bound..=bound {
+            for vy0 in -bound..=bound {
+                let (mut x, mut y, mut vx, mut vy) = (0, 0, vx0, vy0);
+                let mut steps = Vec::new();
+                for step in 1.. {
+                    x += vx;
+                    y += vy;
+                    vx -= vx.signum();
+                    vy -= 1;
+                    if data.x.contains(&x) && data.y.contains(&y) {
+                        steps.push(step);
+                    }
+                    if vy < 0 && y < *data.y.start() {
+                        break;
+                    }
+                }
+                if !steps.is_empty() {
+                    hits.push(Hit {
+                        velocity: (vx0, vy0),
+                        steps,
+                    });
+                }
+            }
+        }
+        hits
+    }
+
+    #[test]
+    fn hits() {
+        let hits = super::solve(&get_example_data()).unwrap();
+        assert_eq!(hits, brute_force(&get_example_data()));
+
+        let hit = |velocity| hits.iter().find(|hit| hit.velocity == velocity);
+        assert_eq!(hit((6, 9)).unwrap().steps, [20]);
+        assert_eq!(hit((7, -1)).unwrap().steps, [4]);
+        assert_eq!(hit((17, -4)), None);
+    }
+
+    #[test]
+    fn any_quadrant() {
+        for (x, y) in [
+            (-30..=-20, -10..=-5),
+            (20..=30, 5..=10),
+            (-30..=-20, 5..=10),
+            (-3..=4, -10..=-5),
+            (-4..=3, 5..=10),
+            (4..=5, -3..=2),
+            (-5..=-4, -2..=3),
+            (0..=0, 1..=1),
+            (200..=210, 90..=100),
+        ] {
+            let data = Data { x, y };
+            assert_eq!(
+                super::solve(&data).unwrap(),
+                brute_force(&data),
+                "{:?}",
+                data
+            );
+        }
+
+        let above = Data {
+            x: 20..=30,
+            y: 5..=10,
+        };
+        assert_eq!(super::day17_part1(&above), 55);
+    }
+
+    #[test]
+    fn unbounded() {
+        // vx = 0 stays at x = 0, and any vy >= 0 comes back down to y = 0
+        assert!(super::solve(&Data {
+            x: -2..=2,
+            y: -1..=1,
+        })
+        .is_err());
+        // vx = 6 stops at x = 21
+        assert!(super::solve(&Data {
+            x: 20..=30,
+            y: -5..=5,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = XorShift(0x5eed_1717);
+        let range = |rng: &mut XorShift| {
+            let a = rng.below(61) as Coord - 30;
+            let b = rng.below(61) as Coord - 30;
+            a.min(b)..=a.max(b)
+        };
+
+        let mut solved = 0;
+        for _ in 0..300 {
+            let data = Data {
+                x: range(&mut rng),
+                y: range(&mut rng),
+            };
+            match super::solve(&data) {
+                Ok(hits) => {
+                    assert_eq!(hits, brute_force(&data), "{:?}", data);
+                    solved += 1;
+                }
+                Err(_) => assert!(data.y.contains(&0), "{:?}", data),
+            }
+        }
+        assert!(solved > 100, "{}", solved);
+    }
 }
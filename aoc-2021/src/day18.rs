@@ -112,104 +112,54 @@ fn reduce_snail(num: &mut Vec<u8>) {
         print_snail(num);
         println!();
     }
-    let mut stack = Vec::new();
-    let mut to_split = Vec::with_capacity(2);
-    let mut done_something;
-    loop {
-        stack.clear();
-        done_something = false;
-        let mut level = 0;
-
-        for (i, &v) in num.iter().enumerate() {
-            // debug_do!{
-            //     print_digit(&v);
-            //     print!(" ");
-            //     print_grid(&[&stack], print_digit);
-            // }
-            if v == BRANCH {
-                stack.push(v);
-                level += 1;
-            } else {
-                let last = stack.last().copied().unwrap();
-                if last == BRANCH {
-                    stack.push(v);
-                } else {
-                    if last == HALF_BRANCH {
-                        stack.pop();
-                        level -= 1;
-                    }
-                    // debug_do!(println!("{}: {}", level, v));
-                    if level > 4 {
-                        done_something = true;
-                        let left = num[i - 1];
-                        debug_do! {
-                            println!("Before explode: [{}, {}]", left, v);
-                            print_snail(num);
-                            println!();
-                        }
-                        if let Some(prev) = num[..i - 1].iter_mut().rposition(|v| *v != BRANCH) {
-                            num[prev] += left;
-                            if num[prev] >= 10 {
-                                to_split.push(prev);
-                            }
-                        }
-                        if let Some(succ) = num[i + 1..].iter_mut().position(|v| *v != BRANCH) {
-                            let succ = i + 1 + succ;
-                            num[succ] += v;
-                            if num[succ] >= 10 {
-                                to_split.push(succ - 2);
-                            }
-                        }
-                        drop(num.splice(i - 2..=i, [0]));
-                        debug_do! {
-                            println!("After explode:");
-                            print_snail(num);
-                            println!();
-                        }
-                        break;
-                    }
-
-                    while stack
-                        .last()
-                        .map(|&last| !matches!(last, BRANCH))
-                        .unwrap_or(false)
-                    {
-                        level -= 1;
-                        let pop = stack.pop().unwrap();
-                        if pop == HALF_BRANCH {
-                            continue;
-                        }
-                        let _branch = stack.pop();
-                        assert_eq!(_branch, Some(BRANCH));
-                    }
-                    if let Some(last_branch) = stack.last_mut() {
-                        assert!(matches!(*last_branch, BRANCH | HALF_BRANCH));
-                        *last_branch = HALF_BRANCH;
-                    }
-                }
-            }
+    while explode_snail(num) || split_snail(num) {
+        debug_do! {
+            print_snail(num);
+            println!();
         }
+    }
+}
+
+fn explode_snail(num: &mut Vec<u8>) -> bool {
+    // number of children still expected by each enclosing pair
+    let mut pending = Vec::new();
 
-        if !to_split.is_empty() {
-            println!("splitting: {:?}", to_split);
-            for (i, mut to_split) in to_split.drain(..).enumerate() {
-                if i == 1 {
-                    to_split += 2;
+    for i in 0..num.len() {
+        if num[i] == BRANCH {
+            if pending.len() >= 4 && num[i + 1] != BRANCH && num[i + 2] != BRANCH {
+                let (left, right) = (num[i + 1], num[i + 2]);
+                if let Some(prev) = num[..i].iter().rposition(|&v| v != BRANCH) {
+                    num[prev] += left;
                 }
-                let value = num[to_split];
-                drop(num.splice(
-                    to_split..=to_split,
-                    [BRANCH, value / 2, value / 2 + value % 2],
-                ));
-                debug_do! {
-                    println!("After split:");
-                    print_snail(num);
-                    println!();
+                if let Some(succ) = num[i + 3..].iter().position(|&v| v != BRANCH) {
+                    num[i + 3 + succ] += right;
                 }
+                drop(num.splice(i..i + 3, [0]));
+                return true;
             }
-        } else if !done_something {
-            break;
+            pending.push(2);
+        } else {
+            while let Some(last) = pending.last_mut() {
+                *last -= 1;
+                if *last > 0 {
+                    break;
+                }
+                pending.pop();
+            }
+        }
+    }
+
+    false
+}
+
+fn split_snail(num: &mut Vec<u8>) -> bool {
+    match num.iter().position(|&v| v != BRANCH && v >= 10) {
+        Some(i) => {
+            let value = num[i];
+            drop(num.splice(i..=i, [BRANCH, value / 2, value - value / 2]));
+            true
         }
+        None => false,
     }
 }
 
@@ -228,8 +178,203 @@ fn sum_snail(data: &[Data]) -> Data {
     Data{number:sum}
 }
 
+type NodeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SnailNode {
+    Regular(u32),
+    Pair(NodeId, NodeId),
+}
+
+/// Arena-backed tree form of a snailfish number.
+///
+/// Unlike the flat [`Data`] encoding, regular numbers are not limited to values below
+/// [`HALF_BRANCH`]. Nodes replaced during reduction are left behind in the arena.
+#[derive(Debug, Clone)]
+pub struct SnailTree {
+    nodes: Vec<SnailNode>,
+    root: NodeId,
+}
+
+impl SnailTree {
+    fn push(&mut self, node: SnailNode) -> NodeId {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    pub fn add(&self, other: &SnailTree) -> SnailTree {
+        let offset = self.nodes.len();
+        let mut nodes = self.nodes.clone();
+        nodes.extend(other.nodes.iter().map(|&node| match node {
+            SnailNode::Regular(v) => SnailNode::Regular(v),
+            SnailNode::Pair(l, r) => SnailNode::Pair(l + offset, r + offset),
+        }));
+
+        let mut sum = SnailTree { nodes, root: 0 };
+        sum.root = sum.push(SnailNode::Pair(self.root, other.root + offset));
+        sum.reduce();
+        sum
+    }
+
+    pub fn reduce(&mut self) {
+        while self.explode(self.root, 0).is_some() || self.split(self.root) {}
+    }
+
+    fn explode(&mut self, id: NodeId, depth: usize) -> Option<(Option<u32>, Option<u32>)> {
+        let (l, r) = match self.nodes[id] {
+            SnailNode::Regular(_) => return None,
+            SnailNode::Pair(l, r) => (l, r),
+        };
+
+        if depth >= 4 {
+            if let (SnailNode::Regular(lv), SnailNode::Regular(rv)) = (self.nodes[l], self.nodes[r])
+            {
+                self.nodes[id] = SnailNode::Regular(0);
+                return Some((Some(lv), Some(rv)));
+            }
+        }
+
+        if let Some((carry_left, carry_right)) = self.explode(l, depth + 1) {
+            if let Some(v) = carry_right {
+                self.add_to_edge(r, v, false);
+            }
+            return Some((carry_left, None));
+        }
+
+        if let Some((carry_left, carry_right)) = self.explode(r, depth + 1) {
+            if let Some(v) = carry_left {
+                self.add_to_edge(l, v, true);
+            }
+            return Some((None, carry_right));
+        }
+
+        None
+    }
+
+    fn add_to_edge(&mut self, mut id: NodeId, value: u32, rightmost: bool) {
+        loop {
+            match &mut self.nodes[id] {
+                SnailNode::Regular(v) => {
+                    *v += value;
+                    return;
+                }
+                SnailNode::Pair(l, r) => id = if rightmost { *r } else { *l },
+            }
+        }
+    }
+
+    fn split(&mut self, id: NodeId) -> bool {
+        match self.nodes[id] {
+            SnailNode::Regular(v) if v >= 10 => {
+                let l = self.push(SnailNode::Regular(v / 2));
+                let r = self.push(SnailNode::Regular(v - v / 2));
+                self.nodes[id] = SnailNode::Pair(l, r);
+                true
+            }
+            SnailNode::Regular(_) => false,
+            SnailNode::Pair(l, r) => self.split(l) || self.split(r),
+        }
+    }
+
+    pub fn magnitude(&self) -> u64 {
+        fn magnitude(tree: &SnailTree, id: NodeId) -> u64 {
+            match tree.nodes[id] {
+                SnailNode::Regular(v) => v as u64,
+                SnailNode::Pair(l, r) => 3 * magnitude(tree, l) + 2 * magnitude(tree, r),
+            }
+        }
+
+        magnitude(self, self.root)
+    }
+
+    fn node_eq(&self, id: NodeId, other: &SnailTree, other_id: NodeId) -> bool {
+        match (self.nodes[id], other.nodes[other_id]) {
+            (SnailNode::Regular(a), SnailNode::Regular(b)) => a == b,
+            (SnailNode::Pair(l1, r1), SnailNode::Pair(l2, r2)) => {
+                self.node_eq(l1, other, l2) && self.node_eq(r1, other, r2)
+            }
+            _ => false,
+        }
+    }
+
+    fn fmt_node(&self, id: NodeId, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.nodes[id] {
+            SnailNode::Regular(v) => write!(f, "{}", v),
+            SnailNode::Pair(l, r) => {
+                f.write_char('[')?;
+                self.fmt_node(l, f)?;
+                f.write_char(',')?;
+                self.fmt_node(r, f)?;
+                f.write_char(']')
+            }
+        }
+    }
+}
+
+impl PartialEq for SnailTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.node_eq(self.root, other, other.root)
+    }
+}
+
+impl Eq for SnailTree {}
+
+impl Display for SnailTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_node(self.root, f)
+    }
+}
+
+impl From<&'_ Data> for SnailTree {
+    fn from(data: &Data) -> Self {
+        fn build(tree: &mut SnailTree, number: &[u8], index: &mut usize) -> NodeId {
+            let v = number[*index];
+            *index += 1;
+            if v == BRANCH {
+                let l = build(tree, number, index);
+                let r = build(tree, number, index);
+                tree.push(SnailNode::Pair(l, r))
+            } else {
+                tree.push(SnailNode::Regular(v as u32))
+            }
+        }
+
+        let mut tree = SnailTree {
+            nodes: Vec::with_capacity(data.number.len()),
+            root: 0,
+        };
+        tree.root = build(&mut tree, &data.number, &mut 0);
+        tree
+    }
+}
+
+impl TryFrom<&'_ SnailTree> for Data {
+    type Error = anyhow::Error;
+
+    fn try_from(tree: &SnailTree) -> Result<Self, Self::Error> {
+        fn flatten(tree: &SnailTree, id: NodeId, number: &mut Vec<u8>) -> anyhow::Result<()> {
+            match tree.nodes[id] {
+                SnailNode::Regular(v) => match u8::try_from(v) {
+                    Ok(v) if v < HALF_BRANCH => number.push(v),
+                    _ => bail!("regular number {} too large for flat encoding", v),
+                },
+                SnailNode::Pair(l, r) => {
+                    number.push(BRANCH);
+                    flatten(tree, l, number)?;
+                    flatten(tree, r, number)?;
+                }
+            }
+            Ok(())
+        }
+
+        let mut number = Vec::new();
+        flatten(tree, tree.root, &mut number)?;
+        Ok(Data { number })
+    }
+}
+
 fn magnitude_snail(data: &Data) -> i32 {
-    todo!()
+    SnailTree::from(data).magnitude() as i32
 }
 
 #[aoc(day18, part1)]
@@ -252,6 +397,7 @@ pub fn day18_part2(data: &[Data]) -> i32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc_utils::rng::XorShift;
 
     const EXAMPLE_INPUTS: &[&str] = &["[[[[4,3],4],4],[7,[[8,4],9]]]\n[1,1]"];
 
@@ -278,7 +424,6 @@ mod tests {
         "[[[[3,0],[5,3]],[4,4]],[5,5]]",
         "[[[[5,0],[7,4]],[5,5]],[6,6]]",
         "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]",
-        "[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]",
     ];
 
     const SUM_EXPANDED_EXAMPLE: &str = "[[[0,[4,5]],[0,0]],[[[4,5],[2,6]],[9,5]]]
@@ -320,13 +465,93 @@ mod tests {
 
         let expanded_example = super::generate(SUM_EXPANDED_EXAMPLE);
         for i in 1..expanded_example.len() {
-            assert_eq!(sum_snail(&expanded_example[..i]), super::generate(SUM_EXPANDED_EXPECTED[i - 1])[0]);
+            assert_eq!(sum_snail(&expanded_example[..=i]), super::generate(SUM_EXPANDED_EXPECTED[i - 1])[0]);
+        }
+    }
+
+    #[test]
+    fn tree_roundtrip() {
+        for line in SUM_EXPANDED_EXAMPLE
+            .lines()
+            .chain(SUM_EXPECTED.iter().copied())
+        {
+            let data = Data::try_from(line).unwrap();
+            let tree = SnailTree::from(&data);
+            assert_eq!(tree.to_string(), line);
+            assert_eq!(Data::try_from(&tree).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn tree_sum() {
+        let expanded_example = super::generate(SUM_EXPANDED_EXAMPLE);
+        let mut sum = SnailTree::from(&expanded_example[0]);
+        for (num, expected) in expanded_example[1..].iter().zip_eq(SUM_EXPANDED_EXPECTED) {
+            sum = sum.add(&SnailTree::from(num));
+            assert_eq!(sum.to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn tree_large_values() {
+        use SnailNode::*;
+
+        let mut tree = SnailTree {
+            nodes: vec![
+                Regular(1),
+                Regular(2),
+                Pair(0, 1),
+                Regular(1000),
+                Pair(2, 3),
+            ],
+            root: 4,
+        };
+        assert_eq!(tree.to_string(), "[[1,2],1000]");
+        assert!(Data::try_from(&tree).is_err());
+
+        tree.nodes[3] = Regular(25);
+        tree.reduce();
+        assert_eq!(tree.to_string(), "[[1,2],[[6,6],[6,7]]]");
+        assert!(Data::try_from(&tree).is_ok());
+    }
+
+    fn random_snail(rng: &mut XorShift, depth: usize, number: &mut Vec<u8>) {
+        if depth < 4 && (depth == 0 || rng.below(3) != 0) {
+            number.push(BRANCH);
+            random_snail(rng, depth + 1, number);
+            random_snail(rng, depth + 1, number);
+        } else {
+            number.push(rng.below(16) as u8);
+        }
+    }
+
+    #[test]
+    fn tree_matches_flat() {
+        let mut rng = XorShift(0x2021_1218);
+        for _ in 0..500 {
+            let numbers = (0..rng.below(5) + 2)
+                .map(|_| {
+                    let mut number = Vec::new();
+                    random_snail(&mut rng, 0, &mut number);
+                    Data { number }
+                })
+                .collect_vec();
+
+            let flat = sum_snail(&numbers);
+            let tree = numbers[1..]
+                .iter()
+                .fold(SnailTree::from(&numbers[0]), |acc, num| {
+                    acc.add(&SnailTree::from(num))
+                });
+
+            assert_eq!(Data::try_from(&tree).unwrap(), flat);
+            assert_eq!(tree, SnailTree::from(&flat));
         }
     }
 
     #[test]
     fn day18_part1() {
-        for (input, data) in EXAMPLE_INPUTS.iter().zip_eq([100]) {
+        for (input, data) in EXAMPLE_INPUTS.iter().zip_eq([1384]) {
             assert_eq!(super::day18_part1(&super::generate(input)), data);
         }
     }
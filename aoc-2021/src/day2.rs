@@ -1,81 +1,256 @@
-use std::convert::TryFrom;
+use std::fmt;
 
 use anyhow::bail;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 
 use aoc_utils::libs::*;
-use aoc_utils::try_from_lines;
 
-#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq)]
-pub enum Movement {
-    Forward(i32),
-    Down(i32),
-    Up(i32),
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Position {
+    pub horizontal: i32,
+    pub depth: i32,
 }
 
-impl TryFrom<&'_ str> for Movement {
-    type Error = anyhow::Error;
+/// Operations commands are built from. Each interpreter decides what they mean.
+pub trait Submarine {
+    /// Moves forwards, or backwards for negative distances.
+    fn advance(&mut self, distance: i32);
+    /// Goes down, or up for negative amounts.
+    fn dive(&mut self, amount: i32);
+    fn surface(&mut self);
+    fn position(&self) -> Position;
+}
+
+/// What a command does, given its distance (`0` for commands without one).
+pub type Action = fn(&mut dyn Submarine, i32);
+
+#[derive(Clone, Copy)]
+struct CommandSpec {
+    name: &'static str,
+    takes_distance: bool,
+    action: Action,
+}
+
+/// Table of known commands, used for parsing.
+#[derive(Clone, Default)]
+pub struct Commands {
+    specs: Vec<CommandSpec>,
+}
+
+impl Commands {
+    /// `forward`, `down`, `up`, `back` and `surface`.
+    pub fn standard() -> Self {
+        let mut commands = Commands::default();
+        commands
+            .register("forward", true, |sub, distance| sub.advance(distance))
+            .register("down", true, |sub, distance| sub.dive(distance))
+            .register("up", true, |sub, distance| sub.dive(-distance))
+            .register("back", true, |sub, distance| sub.advance(-distance))
+            .register("surface", false, |sub, _| sub.surface());
+        commands
+    }
+
+    /// Adds a command, replacing any command with the same name.
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        takes_distance: bool,
+        action: Action,
+    ) -> &mut Self {
+        let spec = CommandSpec {
+            name,
+            takes_distance,
+            action,
+        };
+        match self.specs.iter_mut().find(|spec| spec.name == name) {
+            Some(existing) => *existing = spec,
+            None => self.specs.push(spec),
+        }
+        self
+    }
+
+    fn spec(&self, name: &str) -> Option<&CommandSpec> {
+        self.specs.iter().find(|spec| spec.name == name)
+    }
 
-    fn try_from(line: &str) -> Result<Self, Self::Error> {
-        let (command, distance) = line.split_once(' ').context("split_once")?;
-        let distance = lexical::parse(distance)?;
+    pub fn movement(&self, name: &str, distance: i32) -> Option<Movement> {
+        self.spec(name).map(|spec| Movement {
+            command: spec.name,
+            distance,
+            action: spec.action,
+        })
+    }
 
-        let movement = match command {
-            "forward" => Movement::Forward(distance),
-            "down" => Movement::Down(distance),
-            "up" => Movement::Up(distance),
-            _ => bail!("invalid movement"),
+    pub fn parse_line(&self, line: &str) -> anyhow::Result<Movement> {
+        let mut parts = line.split_ascii_whitespace();
+        let command = parts.next().context("empty command")?;
+        let spec = match self.spec(command) {
+            Some(spec) => spec,
+            None => bail!("unknown command {:?}", command),
         };
+        let distance = if spec.takes_distance {
+            let distance = parts.next().context("missing distance")?;
+            lexical::parse(distance).with_context(|| format!("invalid distance {:?}", distance))?
+        } else {
+            0
+        };
+        if let Some(extra) = parts.next() {
+            bail!("unexpected {:?} after {:?}", extra, command);
+        }
 
-        Ok(movement)
+        Ok(Movement {
+            command: spec.name,
+            distance,
+            action: spec.action,
+        })
+    }
+
+    /// Parses one movement per line, reporting the (1-based) line number of invalid commands.
+    pub fn parse(&self, s: &str) -> anyhow::Result<Vec<Movement>> {
+        s.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                self.parse_line(line)
+                    .with_context(|| format!("line {}", i + 1))
+            })
+            .collect()
     }
 }
 
+/// A parsed command. Movements are equal if they have the same command and distance.
+#[derive(Clone, Copy)]
+pub struct Movement {
+    command: &'static str,
+    distance: i32,
+    action: Action,
+}
+
+impl Movement {
+    pub fn command(&self) -> &'static str {
+        self.command
+    }
+
+    pub fn distance(&self) -> i32 {
+        self.distance
+    }
+
+    pub fn apply(&self, submarine: &mut dyn Submarine) {
+        (self.action)(submarine, self.distance)
+    }
+}
+
+impl PartialEq for Movement {
+    fn eq(&self, other: &Self) -> bool {
+        (self.command, self.distance) == (other.command, other.distance)
+    }
+}
+
+impl Eq for Movement {}
+
+impl fmt::Debug for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.command, self.distance)
+    }
+}
+
+pub fn parse(s: &str) -> anyhow::Result<Vec<Movement>> {
+    Commands::standard().parse(s)
+}
+
 #[aoc_generator(day2)]
 pub fn generate(s: &str) -> Vec<Movement> {
-    try_from_lines(s).expect("couldn't parse input")
+    parse(s).expect("couldn't parse input")
 }
 
-#[aoc(day2, part1)]
-pub fn day2_part1(values: &[Movement]) -> i32 {
-    use Movement::*;
-    let mut depth = 0;
-    let mut horizontal = 0;
-    for value in values {
-        match value {
-            Forward(f) => horizontal += f,
-            Down(d) => depth += d,
-            Up(u) => depth -= u,
-        }
+/// `down`/`up` change the depth directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Direct {
+    position: Position,
+}
+
+impl Submarine for Direct {
+    fn advance(&mut self, distance: i32) {
+        self.position.horizontal += distance;
+    }
+
+    fn dive(&mut self, amount: i32) {
+        self.position.depth += amount;
+    }
+
+    fn surface(&mut self) {
+        self.position.depth = 0;
+    }
+
+    fn position(&self) -> Position {
+        self.position
     }
+}
+
+/// `down`/`up` change the aim, which determines how the depth changes when moving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Aimed {
+    position: Position,
+    aim: i32,
+}
 
+impl Submarine for Aimed {
+    fn advance(&mut self, distance: i32) {
+        self.position.horizontal += distance;
+        self.position.depth += distance * self.aim;
+    }
+
+    fn dive(&mut self, amount: i32) {
+        self.aim += amount;
+    }
+
+    fn surface(&mut self) {
+        self.position.depth = 0;
+        self.aim = 0;
+    }
+
+    fn position(&self) -> Position {
+        self.position
+    }
+}
+
+/// Positions of the submarine before and after every movement.
+pub fn trajectory<S: Submarine + Default>(movements: &[Movement]) -> Vec<Position> {
+    let mut submarine = S::default();
+    let mut positions = Vec::with_capacity(movements.len() + 1);
+    positions.push(submarine.position());
+    for &movement in movements {
+        movement.apply(&mut submarine);
+        positions.push(submarine.position());
+    }
+
+    positions
+}
+
+fn final_position<S: Submarine + Default>(movements: &[Movement]) -> Position {
+    let mut submarine = S::default();
+    for &movement in movements {
+        movement.apply(&mut submarine);
+    }
+
+    submarine.position()
+}
+
+#[aoc(day2, part1)]
+pub fn day2_part1(values: &[Movement]) -> i32 {
+    let Position { horizontal, depth } = final_position::<Direct>(values);
     depth * horizontal
 }
 
 #[aoc(day2, part2)]
 pub fn day2_part2(values: &[Movement]) -> i32 {
-    use Movement::*;
-    let mut depth = 0;
-    let mut horizontal = 0;
-    let mut aim = 0;
-    for value in values {
-        match value {
-            Forward(f) => {
-                horizontal += f;
-                depth += f * aim;
-            }
-            Down(d) => aim += d,
-            Up(u) => aim -= u,
-        }
-    }
-
+    let Position { horizontal, depth } = final_position::<Aimed>(values);
     depth * horizontal
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Movement;
+    use super::{Aimed, Commands, Direct, Movement, Position, Submarine};
 
     const EXAMPLE_INPUT: &str = r#"forward 5
 down 5
@@ -84,9 +259,23 @@ up 3
 down 8
 forward 2"#;
 
+    fn movements(commands: &[(&str, i32)]) -> Vec<Movement> {
+        let standard = Commands::standard();
+        commands
+            .iter()
+            .map(|&(name, distance)| standard.movement(name, distance).unwrap())
+            .collect()
+    }
+
     fn get_example_data() -> Vec<Movement> {
-        use Movement::*;
-        vec![Forward(5), Down(5), Forward(8), Up(3), Down(8), Forward(2)]
+        movements(&[
+            ("forward", 5),
+            ("down", 5),
+            ("forward", 8),
+            ("up", 3),
+            ("down", 8),
+            ("forward", 2),
+        ])
     }
 
     #[test]
@@ -103,4 +292,142 @@ forward 2"#;
     fn day2_part2() {
         assert_eq!(super::day2_part2(&get_example_data()), 900);
     }
+
+    #[test]
+    fn trajectory() {
+        let data = get_example_data();
+        let horizontal = [0, 5, 5, 13, 13, 13, 15];
+        let to_positions = |depths: [i32; 7]| {
+            horizontal
+                .into_iter()
+                .zip(depths)
+                .map(|(horizontal, depth)| Position { horizontal, depth })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            super::trajectory::<Direct>(&data),
+            to_positions([0, 0, 5, 5, 2, 10, 10])
+        );
+        assert_eq!(
+            super::trajectory::<Aimed>(&data),
+            to_positions([0, 0, 0, 40, 40, 40, 60])
+        );
+    }
+
+    #[test]
+    fn extended_commands() {
+        let data = super::generate("down 3\nforward 4\nback 1\nsurface\nforward 2");
+        assert_eq!(
+            data,
+            movements(&[
+                ("down", 3),
+                ("forward", 4),
+                ("back", 1),
+                ("surface", 0),
+                ("forward", 2)
+            ])
+        );
+        assert_eq!(
+            super::trajectory::<Direct>(&data).last(),
+            Some(&Position {
+                horizontal: 5,
+                depth: 0
+            })
+        );
+        assert_eq!(
+            super::trajectory::<Aimed>(&data)[1..],
+            [
+                Position {
+                    horizontal: 0,
+                    depth: 0
+                },
+                Position {
+                    horizontal: 4,
+                    depth: 12
+                },
+                Position {
+                    horizontal: 3,
+                    depth: 9
+                },
+                Position {
+                    horizontal: 3,
+                    depth: 0
+                },
+                Position {
+                    horizontal: 5,
+                    depth: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = super::parse("forward 5\ndown 5\nsideways 3").unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "line 3: unknown command \"sideways\""
+        );
+
+        let error = super::parse("forward 5\ndown x").unwrap_err();
+        assert!(format!("{:#}", error).starts_with("line 2: invalid distance \"x\""));
+
+        let error = super::parse("up").unwrap_err();
+        assert_eq!(format!("{:#}", error), "line 1: missing distance");
+    }
+
+    #[test]
+    fn registered_commands() {
+        let mut commands = Commands::standard();
+        commands
+            .register("crash", false, |sub, _| sub.dive(1000))
+            .register("up", true, |sub, distance| sub.dive(-2 * distance));
+        let data = commands
+            .parse("forward 2\ncrash\nup 250\nforward 1")
+            .unwrap();
+        assert_eq!(data[1].command(), "crash");
+
+        let end = |positions: Vec<Position>| *positions.last().unwrap();
+        assert_eq!(
+            end(super::trajectory::<Direct>(&data)),
+            Position {
+                horizontal: 3,
+                depth: 500
+            }
+        );
+        assert_eq!(
+            end(super::trajectory::<Aimed>(&data)),
+            Position {
+                horizontal: 3,
+                depth: 500
+            }
+        );
+
+        assert!(super::parse("crash").is_err());
+        assert_eq!(
+            format!("{:#}", commands.parse("crash 3").unwrap_err()),
+            "line 1: unexpected \"3\" after \"crash\""
+        );
+
+        // interpreters only need the primitive operations
+        #[derive(Default)]
+        struct Odometer(i32);
+        impl Submarine for Odometer {
+            fn advance(&mut self, distance: i32) {
+                self.0 += distance.abs();
+            }
+            fn dive(&mut self, amount: i32) {
+                self.0 += amount.abs();
+            }
+            fn surface(&mut self) {}
+            fn position(&self) -> Position {
+                Position {
+                    horizontal: self.0,
+                    depth: 0,
+                }
+            }
+        }
+        assert_eq!(end(super::trajectory::<Odometer>(&data)).horizontal, 1503);
+    }
 }
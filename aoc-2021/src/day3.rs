@@ -1,7 +1,10 @@
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 
+use anyhow::ensure;
 use aoc_runner_derive::{aoc, aoc_generator};
+use bitvec::prelude::*;
+use num_bigint::BigUint;
 
 use aoc_utils::libs::*;
 use aoc_utils::try_from_lines;
@@ -15,80 +18,174 @@ impl<'a> TryFrom<&'a str> for Data<'a> {
     type Error = anyhow::Error;
 
     fn try_from(line: &'a str) -> Result<Self, Self::Error> {
+        ensure!(
+            line.bytes().all(|b| matches!(b, b'0' | b'1')),
+            "invalid bit string {:?}",
+            line
+        );
         Ok(Data { val: line })
     }
 }
 
+pub type Bits = BitVec<usize, Msb0>;
+pub type BitsSlice = BitSlice<usize, Msb0>;
+
+impl Data<'_> {
+    pub fn bits(&self) -> Bits {
+        self.val.bytes().map(|b| b == b'1').collect()
+    }
+}
+
 #[aoc_generator(day3)]
 pub fn generate<'input>(s: &'input str) -> Vec<Data<'input>> {
     try_from_lines(s).expect("couldn't parse input")
 }
 
-#[aoc(day3, part1)]
-pub fn day3_part1(values: &[Data<'_>]) -> u32 {
-    let mut counts = vec![0_u16; values[0].val.len()];
-    for d in values {
-        counts
-            .iter_mut()
-            .zip(d.val.as_bytes())
-            .for_each(|(c, &d)| *c += (d == b'1') as u16);
+/// Conversion from a big-endian bit string, failing if the value doesn't fit.
+pub trait FromBits: Sized {
+    fn from_bits(bits: &BitsSlice) -> Option<Self>;
+}
+
+macro_rules! impl_from_bits {
+    ($($t:ty),*) => {$(
+        impl FromBits for $t {
+            fn from_bits(bits: &BitsSlice) -> Option<Self> {
+                if bits.len() > <$t>::BITS as usize {
+                    return None;
+                }
+                Some(bits.iter().fold(0, |acc, b| acc << 1 | *b as $t))
+            }
+        }
+    )*};
+}
+
+impl_from_bits!(u32, u64, u128);
+
+impl FromBits for BigUint {
+    fn from_bits(bits: &BitsSlice) -> Option<Self> {
+        let digits: Vec<u8> = bits.iter().map(|b| *b as u8).collect();
+        BigUint::from_radix_be(&digits, 2)
+    }
+}
+
+/// Keeps the bits as they are, for values too wide for any integer type.
+impl FromBits for Bits {
+    fn from_bits(bits: &BitsSlice) -> Option<Self> {
+        Some(bits.to_bitvec())
     }
+}
 
-    let common = counts
+/// Width shared by all the values, `None` if there are none or the widths differ.
+fn common_width(values: &[impl AsRef<BitsSlice>]) -> Option<usize> {
+    let width = values.first()?.as_ref().len();
+    values
         .iter()
-        .fold(0, |acc, &c| acc * 2 + (c * 2 > values.len() as u16) as u32);
+        .all(|bits| bits.as_ref().len() == width)
+        .then_some(width)
+}
 
-    let uncommon = !common & ((1_u32 << counts.len()) - 1);
+/// Most common value of each bit (`0` on ties) and its complement.
+pub fn gamma_epsilon<T: FromBits>(values: &[Bits]) -> Option<(T, T)> {
+    let width = common_width(values)?;
+    let mut counts = vec![0_usize; width];
+    for bits in values {
+        for index in bits.iter_ones() {
+            counts[index] += 1;
+        }
+    }
+
+    let gamma: Bits = counts.iter().map(|&c| c * 2 > values.len()).collect();
+    let epsilon = !gamma.clone();
 
-    common * uncommon
+    Some((T::from_bits(&gamma)?, T::from_bits(&epsilon)?))
 }
 
-#[aoc(day3, part2)]
-pub fn day3_part2(values: &[Data<'_>]) -> u32 {
-    let mut values = values.to_owned();
-    values.sort_unstable();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Commonness {
+    Most,
+    Least,
+}
+
+/// Which values are kept at each position of the bit criteria filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitCriteria {
+    pub keep: Commonness,
+    /// bit value kept when both values are equally common
+    pub tie: bool,
+}
 
-    fn find_rating<'a>(mut gas_range: &[Data<'a>], rev: bool) -> Data<'a> {
+impl BitCriteria {
+    pub const OXYGEN: BitCriteria = BitCriteria {
+        keep: Commonness::Most,
+        tie: true,
+    };
+    pub const CO2: BitCriteria = BitCriteria {
+        keep: Commonness::Least,
+        tie: false,
+    };
+
+    /// Narrows down the values one bit position at a time, keeping the values whose bit
+    /// matches the criteria, until a single value is left. A position where all remaining
+    /// values have the same bit keeps all of them.
+    ///
+    /// Returns `None` if there are no values or their widths differ.
+    pub fn filter<'v>(&self, values: &'v [Bits]) -> Option<&'v BitsSlice> {
+        common_width(values)?;
+        let mut sorted: Vec<&BitsSlice> = values.iter().map(|bits| bits.as_bitslice()).collect();
+        sorted.sort_unstable();
+
+        let mut range = &sorted[..];
         let mut index = 0;
+        while range.len() > 1 && index < range[0].len() {
+            let zeroes = range.partition_point(|bits| !bits[index]);
+            let (zeroes_range, ones_range) = range.split_at(zeroes);
 
-        while gas_range.len() > 1 {
-            let zeroes = gas_range.partition_point(|d| d.val.as_bytes()[index] == b'0');
-
-            let ord = (zeroes * 2).cmp(&gas_range.len());
-            gas_range = match (rev, ord) {
-                // more common digit or 1s if equal
-                (false, Ordering::Less | Ordering::Equal) => &gas_range[zeroes..],
-                (false, Ordering::Greater) => &gas_range[..zeroes],
-                // less common digit or 0s if equal
-                (true, Ordering::Less | Ordering::Equal) => &gas_range[..zeroes],
-                (true, Ordering::Greater) => &gas_range[zeroes..],
+            let keep_ones = match (zeroes * 2).cmp(&range.len()) {
+                Ordering::Less => self.keep == Commonness::Most,
+                Ordering::Equal => self.tie,
+                Ordering::Greater => self.keep == Commonness::Least,
             };
+            let kept = if keep_ones { ones_range } else { zeroes_range };
+            if !kept.is_empty() {
+                range = kept;
+            }
 
             index += 1;
         }
 
-        gas_range[0]
+        range.first().copied()
     }
+}
 
-    let oxygen = find_rating(&values, false);
-    let co2 = find_rating(&values, true);
+pub fn life_support<T: FromBits>(values: &[Bits]) -> Option<(T, T)> {
+    let oxygen = BitCriteria::OXYGEN.filter(values)?;
+    let co2 = BitCriteria::CO2.filter(values)?;
+    Some((T::from_bits(oxygen)?, T::from_bits(co2)?))
+}
 
-    let oxygen_value = oxygen
-        .val
-        .bytes()
-        .fold(0, |acc, b| acc * 2 + (b == b'1') as u32);
+fn to_bits(values: &[Data<'_>]) -> Vec<Bits> {
+    values.iter().map(Data::bits).collect()
+}
 
-    let co2_value = co2
-        .val
-        .bytes()
-        .fold(0, |acc, b| acc * 2 + (b == b'1') as u32);
+#[aoc(day3, part1)]
+pub fn day3_part1(values: &[Data<'_>]) -> u64 {
+    let (gamma, epsilon) = gamma_epsilon::<u64>(&to_bits(values)).expect("report too wide");
+    gamma
+        .checked_mul(epsilon)
+        .expect("power consumption overflow")
+}
 
-    oxygen_value * co2_value
+#[aoc(day3, part2)]
+pub fn day3_part2(values: &[Data<'_>]) -> u64 {
+    let (oxygen, co2) = life_support::<u64>(&to_bits(values)).expect("report too wide");
+    oxygen
+        .checked_mul(co2)
+        .expect("life support rating overflow")
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{BigUint, BitCriteria, Bits, Commonness, Data};
 
     const EXAMPLE_INPUT: &str = r#"00100
 11110
@@ -134,4 +231,85 @@ mod tests {
     fn day3_part2() {
         assert_eq!(super::day3_part2(&get_example_data()), 230);
     }
+
+    fn bits(s: &str) -> Bits {
+        Data { val: s }.bits()
+    }
+
+    #[test]
+    fn wide_reports() {
+        let mut values = vec![bits(&"10".repeat(50)); 3];
+        values.push(bits(&"01".repeat(50)));
+
+        assert_eq!(super::gamma_epsilon::<u64>(&values), None);
+        let (gamma, epsilon) = super::gamma_epsilon::<u128>(&values).unwrap();
+        assert_eq!(gamma, u128::from_str_radix(&"10".repeat(50), 2).unwrap());
+        assert_eq!(epsilon, u128::from_str_radix(&"01".repeat(50), 2).unwrap());
+
+        let (oxygen, co2) = super::life_support::<Bits>(&values).unwrap();
+        assert_eq!(oxygen, values[0]);
+        assert_eq!(co2, values[3]);
+
+        let values = vec![
+            bits(&"1".repeat(200)),
+            bits(&"0".repeat(200)),
+            bits(&"1".repeat(200)),
+        ];
+        let (gamma, epsilon) = super::gamma_epsilon::<Bits>(&values).unwrap();
+        assert_eq!(gamma, Bits::repeat(true, 200));
+        assert_eq!(epsilon, Bits::repeat(false, 200));
+
+        let (gamma, epsilon) = super::gamma_epsilon::<BigUint>(&values).unwrap();
+        assert_eq!(gamma, (BigUint::from(1_u8) << 200) - 1_u8);
+        assert_eq!(epsilon, BigUint::default());
+        assert_eq!(gamma.bits(), 200);
+
+        let values = vec![bits(&"10".repeat(100)), bits(&"1".repeat(200))];
+        let (oxygen, co2) = super::life_support::<BigUint>(&values).unwrap();
+        assert_eq!(oxygen, (BigUint::from(1_u8) << 200) - 1_u8);
+        assert_eq!(co2, BigUint::from_radix_be(&[1, 0].repeat(100), 2).unwrap());
+    }
+
+    #[test]
+    fn many_lines() {
+        // the lowest four bits are 1 in exactly half of the lines, and ties give 0
+        let values: Vec<_> = (0..70_000_u32)
+            .map(|i| bits(&format!("{:017b}", i)))
+            .collect();
+        assert_eq!(
+            super::gamma_epsilon::<u32>(&values),
+            Some((0, 0b1_1111_1111_1111_1111))
+        );
+        let values: Vec<_> = (1..70_000_u32)
+            .map(|i| bits(&format!("{:017b}", i)))
+            .collect();
+        assert_eq!(
+            super::gamma_epsilon::<u32>(&values),
+            Some((0b0_0000_0000_0000_1111, 0b1_1111_1111_1111_0000))
+        );
+    }
+
+    #[test]
+    fn ragged_reports() {
+        let values = vec![bits("10110"), bits("101"), bits("10111")];
+        assert_eq!(super::gamma_epsilon::<u32>(&values), None);
+        assert_eq!(super::life_support::<u32>(&values), None);
+        assert_eq!(BitCriteria::CO2.filter(&values), None);
+    }
+
+    #[test]
+    fn bit_criteria() {
+        let values: Vec<_> = get_example_data().iter().map(Data::bits).collect();
+        let filter = |keep, tie| {
+            BitCriteria { keep, tie }
+                .filter(&values)
+                .map(|bits| bits.to_bitvec())
+        };
+
+        assert_eq!(filter(Commonness::Most, true), Some(bits("10111")));
+        assert_eq!(filter(Commonness::Least, false), Some(bits("01010")));
+        assert_eq!(filter(Commonness::Most, false), Some(bits("10110")));
+        assert_eq!(filter(Commonness::Least, true), Some(bits("01111")));
+        assert_eq!(BitCriteria::OXYGEN.filter(&[]), None);
+    }
 }
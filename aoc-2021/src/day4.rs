@@ -13,101 +13,153 @@ use aoc_utils::try_from_lines;
 
 #[derive(Debug, Clone, PartialOrd, PartialEq, Eq)]
 pub struct Board {
-    numbers: [[u8; 5]; 5],
+    width: usize,
+    height: usize,
+    /// row-major
+    numbers: Vec<u32>,
+}
+
+impl Board {
+    pub fn from_rows(rows: Vec<Vec<u32>>) -> anyhow::Result<Board> {
+        let width = rows.first().map_or(0, Vec::len);
+        if width == 0 {
+            bail!("empty board");
+        }
+        if let Some(row) = rows.iter().position(|row| row.len() != width) {
+            bail!(
+                "row {} has {} numbers, expected {}",
+                row + 1,
+                rows[row].len(),
+                width
+            );
+        }
+
+        Ok(Board {
+            width,
+            height: rows.len(),
+            numbers: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Indices into `numbers` of every line that wins when fully marked.
+    fn lines(&self, diagonals: bool) -> Vec<Vec<usize>> {
+        let (width, height) = (self.width, self.height);
+        let rows = (0..height).map(|y| (0..width).map(|x| y * width + x).collect());
+        let columns = (0..width).map(|x| (0..height).map(|y| y * width + x).collect());
+        let mut lines: Vec<Vec<usize>> = rows.chain(columns).collect();
+        if diagonals && width == height {
+            lines.push((0..width).map(|i| i * width + i).collect());
+            lines.push((0..width).map(|i| i * width + width - 1 - i).collect());
+        }
+        lines
+    }
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq, Eq)]
 pub struct Data {
-    drawn: Vec<u8>,
+    drawn: Vec<u32>,
     boards: Vec<Board>,
 }
 
 #[aoc_generator(day4)]
 pub fn generate(s: &str) -> Data {
-    let (drawn, boards) = s.split_once("\n\n").unwrap();
-    let drawn = drawn.split(',').map(|n| n.parse().unwrap()).collect_vec();
+    let (drawn, boards) = s.trim().split_once("\n\n").unwrap();
+    let drawn = drawn
+        .split(',')
+        .map(|n| n.trim().parse().unwrap())
+        .collect_vec();
 
     let boards = boards
         .split("\n\n")
         .map(|board_str| {
-            let mut numbers = [[0; 5]; 5];
-
-            board_str
-                .split_ascii_whitespace()
-                .zip(numbers.iter_mut().flatten())
-                .for_each(|(s, n)| *n = s.parse().unwrap());
+            let rows = board_str
+                .lines()
+                .map(|line| {
+                    line.split_ascii_whitespace()
+                        .map(|n| n.parse().unwrap())
+                        .collect()
+                })
+                .collect();
 
-            Board { numbers }
+            Board::from_rows(rows).unwrap()
         })
         .collect();
 
     Data { boards, drawn }
 }
 
-fn winning_value(when_drawn: &[usize; 100], board: &Board) -> (i32, usize) {
-    let mut when_row = [0; 5];
-    let mut when_column = [0; 5];
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Win {
+    pub board: usize,
+    /// index into the drawn numbers of the number completing the board
+    pub draw_index: usize,
+    /// sum of the unmarked numbers multiplied by the number completing the board
+    pub score: u64,
+}
 
-    for (i, row) in board.numbers.iter().enumerate() {
-        for (j, &v) in row.iter().enumerate() {
-            let v_drawn = when_drawn[v as usize];
-            when_row[i] = when_row[i].max(v_drawn);
-            when_column[j] = when_column[j].max(v_drawn);
-        }
-    }
+fn board_win(
+    when_drawn: &HashMap<u32, usize>,
+    board: &Board,
+    diagonals: bool,
+) -> Option<(usize, u64)> {
+    let when = |v: &u32| when_drawn.get(v).copied().unwrap_or(usize::MAX);
 
-    let when_won = when_row
+    let when_won = board
+        .lines(diagonals)
         .iter()
-        .chain(when_column.iter())
-        .copied()
+        .map(|line| line.iter().map(|&i| when(&board.numbers[i])).max().unwrap())
         .min()
-        .unwrap();
+        .filter(|&when_won| when_won != usize::MAX)?;
 
-    let value = board
+    let unmarked: u64 = board
         .numbers
         .iter()
-        .flatten()
-        .copied()
-        .filter(|&v| when_drawn[v as usize] > when_won)
-        // might overflow on `u8`
-        .map(|v| v as i32)
+        .filter(|&v| when(v) > when_won)
+        .map(|&v| v as u64)
         .sum();
 
-    (value, when_won)
+    Some((when_won, unmarked))
 }
 
-fn first_last_winning_value(data: &Data) -> (i32, i32) {
-    let mut when_drawn = [usize::MAX; 100];
+/// Every board that eventually wins, in the order of winning. Boards winning on the same
+/// number are ordered by their position in the input.
+pub fn win_order(data: &Data, diagonals: bool) -> Vec<Win> {
+    let mut when_drawn = HashMap::new();
     for (i, &d) in data.drawn.iter().enumerate() {
-        when_drawn[d as usize] = i;
+        when_drawn.entry(d).or_insert(i);
     }
 
-    let (first, last) = data
+    let mut wins = data
         .boards
         .iter()
-        .map(|board| winning_value(&when_drawn, board))
-        .minmax_by_key(|&(_, when)| when)
-        .into_option()
-        .unwrap();
-
-    let value = |(winning_board_value, when_won)| winning_board_value * data.drawn[when_won] as i32;
+        .enumerate()
+        .filter_map(|(i, board)| {
+            let (draw_index, unmarked) = board_win(&when_drawn, board, diagonals)?;
+            Some(Win {
+                board: i,
+                draw_index,
+                score: unmarked * data.drawn[draw_index] as u64,
+            })
+        })
+        .collect_vec();
+    wins.sort_by_key(|win| win.draw_index);
 
-    (value(first), value(last))
+    wins
 }
 
 #[aoc(day4, part1)]
-pub fn day4_part1(data: &Data) -> i32 {
-    first_last_winning_value(data).0
+pub fn day4_part1(data: &Data) -> u64 {
+    win_order(data, false).first().unwrap().score
 }
 
 #[aoc(day4, part2)]
-pub fn day4_part2(data: &Data) -> i32 {
-    first_last_winning_value(data).1
+pub fn day4_part2(data: &Data) -> u64 {
+    win_order(data, false).last().unwrap().score
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Board, Data};
+    use super::{Board, Data, Win};
 
     const EXAMPLE_INPUT: &str = r#"7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
 
@@ -136,33 +188,30 @@ mod tests {
                 19, 3, 26, 1,
             ],
             boards: vec![
-                Board {
-                    numbers: [
-                        [22, 13, 17, 11, 0],
-                        [8, 2, 23, 4, 24],
-                        [21, 9, 14, 16, 7],
-                        [6, 10, 3, 18, 5],
-                        [1, 12, 20, 15, 19],
-                    ],
-                },
-                Board {
-                    numbers: [
-                        [3, 15, 0, 2, 22],
-                        [9, 18, 13, 17, 5],
-                        [19, 8, 7, 25, 23],
-                        [20, 11, 10, 24, 4],
-                        [14, 21, 16, 12, 6],
-                    ],
-                },
-                Board {
-                    numbers: [
-                        [14, 21, 17, 24, 4],
-                        [10, 16, 15, 9, 19],
-                        [18, 8, 23, 26, 20],
-                        [22, 11, 13, 6, 5],
-                        [2, 0, 12, 3, 7],
-                    ],
-                },
+                Board::from_rows(vec![
+                    vec![22, 13, 17, 11, 0],
+                    vec![8, 2, 23, 4, 24],
+                    vec![21, 9, 14, 16, 7],
+                    vec![6, 10, 3, 18, 5],
+                    vec![1, 12, 20, 15, 19],
+                ])
+                .unwrap(),
+                Board::from_rows(vec![
+                    vec![3, 15, 0, 2, 22],
+                    vec![9, 18, 13, 17, 5],
+                    vec![19, 8, 7, 25, 23],
+                    vec![20, 11, 10, 24, 4],
+                    vec![14, 21, 16, 12, 6],
+                ])
+                .unwrap(),
+                Board::from_rows(vec![
+                    vec![14, 21, 17, 24, 4],
+                    vec![10, 16, 15, 9, 19],
+                    vec![18, 8, 23, 26, 20],
+                    vec![22, 11, 13, 6, 5],
+                    vec![2, 0, 12, 3, 7],
+                ])
+                .unwrap(),
             ],
         }
     }
@@ -181,4 +230,71 @@ mod tests {
     fn day4_part2() {
         assert_eq!(super::day4_part2(&get_example_data()), 1924);
     }
+
+    #[test]
+    fn win_order() {
+        assert_eq!(
+            super::win_order(&get_example_data(), false),
+            [
+                Win {
+                    board: 2,
+                    draw_index: 11,
+                    score: 4512
+                },
+                Win {
+                    board: 0,
+                    draw_index: 13,
+                    score: 2192
+                },
+                Win {
+                    board: 1,
+                    draw_index: 14,
+                    score: 1924
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rectangular_boards() {
+        let data = super::generate(
+            "1000,7,300,2,5\n\
+             \n\
+             1000 7 300\n\
+             2 9 11\n\
+             \n\
+             7 2\n\
+             5 8\n\
+             300 1",
+        );
+        assert_eq!(
+            super::win_order(&data, false),
+            [
+                Win {
+                    board: 0,
+                    draw_index: 2,
+                    score: 22 * 300
+                },
+                Win {
+                    board: 1,
+                    draw_index: 3,
+                    score: 14 * 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diagonals() {
+        let data = super::generate("5,1,9\n\n1 2 3\n4 5 6\n7 8 9\n\n1 2\n3 4");
+        assert_eq!(
+            super::win_order(&data, true),
+            [Win {
+                board: 0,
+                draw_index: 2,
+                score: (2 + 3 + 4 + 6 + 7 + 8) * 9
+            }]
+        );
+        assert_eq!(super::win_order(&data, false), []);
+    }
 }
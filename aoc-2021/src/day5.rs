@@ -1,8 +1,11 @@
 #![allow(unused_imports)]
 
-use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::str::FromStr;
 
+use anyhow::bail;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
@@ -10,41 +13,133 @@ use itertools::Itertools;
 use aoc_utils::libs::*;
 use aoc_utils::try_from_lines;
 
-type Coord = u16;
-type Count = u8;
+/// Input coordinates: any integer type which fits in [`Coord`], up to `i64` and `u64`.
+pub trait Coordinate: Copy + Ord + Debug + FromStr + Into<Coord> {}
 
-#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq)]
-struct Point {
-    x: Coord,
-    y: Coord,
+impl<T: Copy + Ord + Debug + FromStr + Into<Coord>> Coordinate for T {}
+
+/// Coordinates used in computations. Keys and intersections take sums and differences of
+/// two input coordinates, so this needs at least one bit more than any [`Coordinate`].
+type Coord = i128;
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq, Hash)]
+struct Point<C = Coord> {
+    x: C,
+    y: C,
+}
+
+impl<C: Coordinate> Point<C> {
+    fn widen(self) -> Point {
+        Point {
+            x: self.x.into(),
+            y: self.y.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Orientation {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+}
+
+impl Orientation {
+    /// `(a, b)` such that every point of a line satisfies `a * x + b * y = key`.
+    fn normal(self) -> (Coord, Coord) {
+        match self {
+            Orientation::Horizontal => (0, 1),
+            Orientation::Vertical => (1, 0),
+            Orientation::Diagonal => (1, -1),
+            Orientation::AntiDiagonal => (1, 1),
+        }
+    }
+
+    fn key(self, p: Point) -> Coord {
+        let (a, b) = self.normal();
+        a * p.x + b * p.y
+    }
+
+    /// Inverse of [`Orientation::key`] and [`Orientation::param`].
+    fn point(self, key: Coord, param: Coord) -> Point {
+        let (x, y) = match self {
+            Orientation::Horizontal => (param, key),
+            Orientation::Vertical => (key, param),
+            Orientation::Diagonal => (param, param - key),
+            Orientation::AntiDiagonal => (param, key - param),
+        };
+        Point { x, y }
+    }
+
+    /// Position of a point along its line.
+    fn param(self, p: Point) -> Coord {
+        match self {
+            Orientation::Vertical => p.y,
+            _ => p.x,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq)]
-pub struct Data {
-    from: Point,
-    to: Point,
+pub struct Data<C = i64> {
+    from: Point<C>,
+    to: Point<C>,
 }
 
-impl Data {
-    fn new(x1: Coord, y1: Coord, x2: Coord, y2: Coord) -> Self {
+impl<C: Coordinate> Data<C> {
+    fn new(x1: C, y1: C, x2: C, y2: C) -> Self {
         Data {
             from: Point { x: x1, y: y1 },
             to: Point { x: x2, y: y2 },
         }
     }
+
+    fn widen(&self) -> (Point, Point) {
+        (self.from.widen(), self.to.widen())
+    }
+
+    /// `None` unless the segment is axis-aligned or at 45°. Single points count as horizontal.
+    fn orientation(&self) -> Option<Orientation> {
+        let (from, to) = self.widen();
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        if dy == 0 {
+            Some(Orientation::Horizontal)
+        } else if dx == 0 {
+            Some(Orientation::Vertical)
+        } else if dx == dy {
+            Some(Orientation::Diagonal)
+        } else if dx == -dy {
+            Some(Orientation::AntiDiagonal)
+        } else {
+            None
+        }
+    }
 }
 
-impl TryFrom<&'_ str> for Data {
+impl<C: Coordinate> TryFrom<&'_ str> for Data<C> {
     type Error = anyhow::Error;
 
     fn try_from(line: &str) -> Result<Self, Self::Error> {
-        let (x1, y1, x2, y2) = line
-            .split(|c: char| !c.is_ascii_digit())
-            .filter_map(|s| s.parse().ok())
-            .collect_tuple()
-            .context("wrong number of values")?;
+        let parse = |s: &str| -> anyhow::Result<C> {
+            s.trim()
+                .parse()
+                .ok()
+                .with_context(|| format!("invalid coordinate {:?}", s))
+        };
+        let parse_point = |s: &str| -> anyhow::Result<_> {
+            let (x, y) = s.split_once(',').context("missing comma")?;
+            Ok((parse(x)?, parse(y)?))
+        };
 
-        Ok(Data::new(x1, y1, x2, y2))
+        let (from, to) = line.split_once("->").context("missing arrow")?;
+        let ((x1, y1), (x2, y2)) = (parse_point(from)?, parse_point(to)?);
+        let data = Data::new(x1, y1, x2, y2);
+        if data.orientation().is_none() {
+            bail!("segment is neither axis-aligned nor diagonal: {}", line);
+        }
+
+        Ok(data)
     }
 }
 
@@ -53,99 +148,230 @@ pub fn generate(s: &str) -> Vec<Data> {
     try_from_lines(s).expect("couldn't parse input")
 }
 
-trait DispatchIterator {
-    fn run<I: IntoIterator<Item = Coord>>(self, i: I);
+/// Maximal stretch of a line covered by the same number of segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Run {
+    start: Coord,
+    end: Coord,
+    depth: usize,
 }
 
-fn dispatch_range(from: Coord, to: Coord, dispatch: impl DispatchIterator) {
-    match from.cmp(&to) {
-        Ordering::Less => dispatch.run(from..=to),
-        Ordering::Equal => dispatch.run(std::iter::repeat(from)),
-        Ordering::Greater => dispatch.run((to..=from).rev()),
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Overlaps {
+    /// number of points covered by at least two segments, which only fits in a `u128` for
+    /// 64-bit coordinates
+    pub points: u128,
+    /// largest number of segments covering a single point
+    pub max_depth: usize,
 }
 
-fn iter_lines(data: &Data, allow_diagonal: bool, f: impl FnMut((Coord, Coord))) {
-    let &Data {
-        from: Point { x: x1, y: y1 },
-        to: Point { x: x2, y: y2 },
-    } = data;
+/// Merges collinear segments into runs by sweeping over their sorted endpoints.
+fn collinear_runs(segments: &[(Coord, Coord)]) -> Vec<Run> {
+    let events = segments
+        .iter()
+        .flat_map(|&(start, end)| [(start, 1), (end + 1, -1)])
+        .sorted()
+        .collect_vec();
 
-    struct DispatchXs<F>(Coord, Coord, F);
-    impl<F: FnMut((Coord, Coord))> DispatchIterator for DispatchXs<F> {
-        fn run<I: IntoIterator<Item = Coord>>(self, i: I) {
-            dispatch_range(self.0, self.1, DispatchYs(i, self.2))
+    let mut runs = Vec::new();
+    let mut depth = 0;
+    for (i, &(at, change)) in events.iter().enumerate() {
+        depth += change;
+        let next = events.get(i + 1).map(|&(next, _)| next);
+        if depth > 0 && next.is_some_and(|next| next > at) {
+            runs.push(Run {
+                start: at,
+                end: next.unwrap() - 1,
+                depth: depth as usize,
+            });
         }
     }
 
-    struct DispatchYs<I, F>(I, F);
-    impl<I2: IntoIterator<Item = Coord>, F: FnMut((Coord, Coord))> DispatchIterator
-        for DispatchYs<I2, F>
-    {
-        fn run<I: IntoIterator<Item = Coord>>(self, i: I) {
-            self.0.into_iter().zip(i).for_each(self.1)
+    runs
+}
+
+/// Segments of lines of two orientations, in coordinates where the first orientation's
+/// lines are vertical and the second's horizontal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    /// a horizontal segment on line `v` stops covering this column
+    Remove { v: Coord },
+    /// a horizontal segment on line `v` starts covering this column
+    Add { v: Coord },
+    /// a vertical segment covers `v_start..=v_end` in this column
+    Query { v_start: Coord, v_end: Coord },
+}
+
+/// Points where runs of lines with orientations `o1` and `o2` cross.
+///
+/// Sweeps over the `o1` keys, keeping the `o2` runs covering the current key in an ordered
+/// map, so this takes `O((R + K) log R)` for `R` runs and `K` crossings of runs (including
+/// crossings between lattice points, which are skipped).
+fn crossings(
+    (o1, lines1): (Orientation, &[(Coord, &[Run])]),
+    (o2, lines2): (Orientation, &[(Coord, &[Run])]),
+    mut found: impl FnMut(Point),
+) {
+    // along any line the other orientation's key is strictly monotonic in the parameter
+    let key_range = |o: Orientation, key, run: &Run, other: Orientation| {
+        let a = other.key(o.point(key, run.start));
+        let b = other.key(o.point(key, run.end));
+        (a.min(b), a.max(b))
+    };
+
+    let mut events = Vec::new();
+    for &(u, runs) in lines1 {
+        for run in runs {
+            let (v_start, v_end) = key_range(o1, u, run, o2);
+            events.push((u, Event::Query { v_start, v_end }));
+        }
+    }
+    for &(v, runs) in lines2 {
+        for run in runs {
+            let (u_start, u_end) = key_range(o2, v, run, o1);
+            events.push((u_start, Event::Add { v }));
+            events.push((u_end + 1, Event::Remove { v }));
         }
     }
+    events.sort_unstable();
 
-    if allow_diagonal || (x1 == x2 || y1 == y2) {
-        dispatch_range(x1, x2, DispatchXs(y1, y2, f));
+    let mut active = BTreeMap::<Coord, usize>::new();
+    for (u, event) in events {
+        match event {
+            Event::Add { v } => *active.entry(v).or_default() += 1,
+            Event::Remove { v } => {
+                let count = active.get_mut(&v).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    active.remove(&v);
+                }
+            }
+            Event::Query { v_start, v_end } => {
+                for &v in active.range(v_start..=v_end).map(|(v, _)| v) {
+                    if let Some(p) = line_intersection(o1, u, o2, v) {
+                        found(p);
+                    }
+                }
+            }
+        }
     }
 }
 
-fn fill_counts(
-    data: &Data,
-    counts: &mut HashMap<(Coord, Coord), Count>,
-    allow_diagonal: bool,
-) -> i32 {
-    let mut overlapping = 0;
-    iter_lines(data, allow_diagonal, |(x, y)| {
-        let entry = counts.entry((x, y)).or_insert(0);
-        *entry += 1;
-        if *entry == 2 {
-            overlapping += 1;
+fn line_intersection(o1: Orientation, k1: Coord, o2: Orientation, k2: Coord) -> Option<Point> {
+    let ((a1, b1), (a2, b2)) = (o1.normal(), o2.normal());
+    let det = a1 * b2 - a2 * b1;
+    let (x, y) = (k1 * b2 - k2 * b1, a1 * k2 - a2 * k1);
+    (det != 0 && x % det == 0 && y % det == 0).then(|| Point {
+        x: x / det,
+        y: y / det,
+    })
+}
+
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::Horizontal,
+    Orientation::Vertical,
+    Orientation::Diagonal,
+    Orientation::AntiDiagonal,
+];
+
+/// Counts overlaps without rasterising: collinear segments are merged per line, and the
+/// remaining overlaps can only happen where lines of different orientations cross, which
+/// are found with a sweep for each pair of orientations.
+pub fn overlaps<C: Coordinate>(data: &[Data<C>], allow_diagonal: bool) -> Overlaps {
+    let mut lines: HashMap<(Orientation, Coord), Vec<(Coord, Coord)>> = HashMap::default();
+    for d in data {
+        let orientation = d.orientation().expect("unsupported segment");
+        if !allow_diagonal
+            && matches!(
+                orientation,
+                Orientation::Diagonal | Orientation::AntiDiagonal
+            )
+        {
+            continue;
         }
-    });
+        let (from, to) = d.widen();
+        let (p1, p2) = (orientation.param(from), orientation.param(to));
+        lines
+            .entry((orientation, orientation.key(from)))
+            .or_default()
+            .push((p1.min(p2), p1.max(p2)));
+    }
 
-    overlapping
-}
+    let lines: HashMap<_, _> = lines
+        .into_iter()
+        .map(|(line, segments)| (line, collinear_runs(&segments)))
+        .collect();
 
-fn _print_counts(counts: &HashMap<(Coord, Coord), Count>) {
-    let (x_max, y_max) = counts.keys().fold((0, 0), |(x_max, y_max), &(x, y)| {
-        (x_max.max(x), y_max.max(y))
-    });
-    for y in 0..=y_max {
-        for x in 0..=x_max {
-            print!("{} ", counts.get(&(x, y)).copied().unwrap_or(0));
+    let mut result = Overlaps::default();
+    for run in lines.values().flatten() {
+        result.max_depth = result.max_depth.max(run.depth);
+        if run.depth >= 2 {
+            result.points += (run.end - run.start + 1) as u128;
         }
-        println!()
     }
-}
 
-fn day5(data: &[Data], allow_diagonal: bool) -> i32 {
-    let mut counts = HashMap::default();
-    let mut overlapping = 0;
+    let depth_at = |orientation: Orientation, p: Point| {
+        let runs = match lines.get(&(orientation, orientation.key(p))) {
+            Some(runs) => runs,
+            None => return 0,
+        };
+        let t = orientation.param(p);
+        let i = runs.partition_point(|run| run.end < t);
+        runs.get(i)
+            .filter(|run| run.start <= t)
+            .map_or(0, |run| run.depth)
+    };
 
-    for d in data.iter() {
-        overlapping += fill_counts(d, &mut counts, allow_diagonal);
+    let by_orientation = ORIENTATIONS.map(|o| {
+        lines
+            .iter()
+            .filter(|((orientation, _), _)| *orientation == o)
+            .map(|(&(_, key), runs)| (key, runs.as_slice()))
+            .collect_vec()
+    });
+    let mut points = HashSet::default();
+    for (i, j) in (0..ORIENTATIONS.len()).tuple_combinations() {
+        crossings(
+            (ORIENTATIONS[i], &by_orientation[i]),
+            (ORIENTATIONS[j], &by_orientation[j]),
+            |p| {
+                points.insert(p);
+            },
+        );
+    }
+
+    for p in points {
+        let depths = ORIENTATIONS.map(|o| depth_at(o, p));
+        result.max_depth = result.max_depth.max(depths.iter().sum());
+        // points with a collinear overlap were already counted with their run
+        if depths.iter().all(|&depth| depth < 2) {
+            result.points += 1;
+        }
     }
 
-    // _print_counts(&counts);
-    overlapping
+    result
 }
 
 #[aoc(day5, part1)]
-pub fn day5_part1(data: &[Data]) -> i32 {
-    day5(data, false)
+pub fn day5_part1(data: &[Data]) -> u128 {
+    overlaps(data, false).points
 }
 
 #[aoc(day5, part2)]
-pub fn day5_part2(data: &[Data]) -> i32 {
-    day5(data, true)
+pub fn day5_part2(data: &[Data]) -> u128 {
+    overlaps(data, true).points
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Data, Overlaps, Point};
+    use aoc_utils::libs::HashMap;
+    use aoc_utils::rng::XorShift;
+    use std::cmp::Ordering;
+    use std::convert::TryFrom;
+
+    /// coordinates of the default [`Data`]
+    type Coord = i64;
 
     const EXAMPLE_INPUT: &str = r#"0,9 -> 5,9
 8,0 -> 0,8
@@ -187,4 +413,240 @@ mod tests {
     fn day5_part2() {
         assert_eq!(super::day5_part2(&get_example_data()), 12);
     }
+
+    #[test]
+    fn max_depth() {
+        let data = get_example_data();
+        assert_eq!(
+            super::overlaps(&data, false),
+            Overlaps {
+                points: 5,
+                max_depth: 2
+            }
+        );
+        assert_eq!(
+            super::overlaps(&data, true),
+            Overlaps {
+                points: 12,
+                max_depth: 3
+            }
+        );
+    }
+
+    #[test]
+    fn deep_and_long_overlaps() {
+        let mut data = vec![Data::new(-1_000_000_000, 7, 1_000_000_000, 7); 300];
+        data.push(Data::new(0, 0, 0, 10));
+        data.push(Data::new(-3, 10, 3, 4));
+        assert_eq!(
+            super::overlaps(&data, true),
+            Overlaps {
+                points: 2_000_000_001,
+                max_depth: 302
+            }
+        );
+    }
+
+    #[test]
+    fn diagonal_parity() {
+        // crossing between lattice points
+        let data = super::generate("0,0 -> 3,3\n0,1 -> 3,-2");
+        assert_eq!(
+            super::overlaps(&data, true),
+            Overlaps {
+                points: 0,
+                max_depth: 1
+            }
+        );
+
+        let data = super::generate("0,0 -> 4,4\n0,4 -> 4,0\n2,2 -> 2,2");
+        assert_eq!(
+            super::overlaps(&data, true),
+            Overlaps {
+                points: 1,
+                max_depth: 3
+            }
+        );
+    }
+
+    #[test]
+    fn coordinate_types() {
+        let data = get_example_data();
+        let narrow = data
+            .iter()
+            .map(|d| {
+                let narrow = |c: Coord| c as i32;
+                Data::new(
+                    narrow(d.from.x),
+                    narrow(d.from.y),
+                    narrow(d.to.x),
+                    narrow(d.to.y),
+                )
+            })
+            .collect::<Vec<Data<i32>>>();
+        assert_eq!(super::overlaps(&narrow, true), super::overlaps(&data, true));
+
+        // keys of extreme i32 coordinates don't fit in i32
+        let data: Vec<Data<i32>> = vec![
+            Data::try_from("2147483647,2147483647 -> 2147483640,2147483640").unwrap(),
+            Data::try_from("2147483647,2147483640 -> 2147483640,2147483647").unwrap(),
+            Data::try_from("-2147483648,0 -> 2147483647,0").unwrap(),
+        ];
+        assert_eq!(super::overlaps(&data, true).max_depth, 1);
+        assert!(Data::<u8>::try_from("0,0 -> 256,0").is_err());
+    }
+
+    #[test]
+    fn extreme_i64_coordinates() {
+        let (min, max) = (i64::MIN, i64::MAX);
+        let data = vec![
+            // overlapping across the whole range
+            Data::new(min, 0, max, 0),
+            Data::new(min, 0, max, 0),
+            // crossings whose keys and intersections don't fit in i64
+            Data::new(max, max, max - 8, max - 8),
+            Data::new(max, max - 8, max - 8, max),
+            Data::new(min, min, min + 8, min + 8),
+            Data::new(min, min + 8, min + 8, min),
+            // through the first crossing and the overlapping run
+            Data::new(max - 4, min, max - 4, max),
+        ];
+        assert_eq!(
+            super::overlaps(&data, true),
+            Overlaps {
+                points: (1 << 64) + 2,
+                max_depth: 3
+            }
+        );
+
+        let parsed: Vec<Data> = vec![
+            Data::try_from("9223372036854775807,0 -> -9223372036854775808,0").unwrap(),
+            Data::try_from("0,9223372036854775807 -> 0,-9223372036854775808").unwrap(),
+        ];
+        assert_eq!(
+            super::overlaps(&parsed, false),
+            Overlaps {
+                points: 1,
+                max_depth: 2
+            }
+        );
+
+        let unsigned: Vec<Data<u64>> = vec![
+            Data::new(u64::MAX, 0, 0, u64::MAX),
+            Data::new(0, 0, u64::MAX, u64::MAX),
+        ];
+        assert_eq!(super::overlaps(&unsigned, true).max_depth, 1);
+    }
+
+    #[test]
+    fn many_lines_without_crossings() {
+        // the runs of each orientation pair are disjoint, so the sweep finds no crossings
+        let data = (0..20_000)
+            .flat_map(|k| {
+                [
+                    Data::new(0, k, 10, k),
+                    Data::new(100 + k, -1000, 100 + k, -990),
+                ]
+            })
+            .collect::<Vec<Data>>();
+        assert_eq!(
+            super::overlaps(&data, false),
+            Overlaps {
+                points: 0,
+                max_depth: 1
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_other_angles() {
+        assert!(<Data as std::convert::TryFrom<_>>::try_from("0,0 -> 1,2").is_err());
+    }
+
+    trait DispatchIterator {
+        fn run<I: IntoIterator<Item = Coord>>(self, i: I);
+    }
+
+    fn dispatch_range(from: Coord, to: Coord, dispatch: impl DispatchIterator) {
+        match from.cmp(&to) {
+            Ordering::Less => dispatch.run(from..=to),
+            Ordering::Equal => dispatch.run(std::iter::repeat(from)),
+            Ordering::Greater => dispatch.run((to..=from).rev()),
+        }
+    }
+
+    fn iter_lines(data: &Data, allow_diagonal: bool, f: impl FnMut((Coord, Coord))) {
+        let &Data {
+            from: Point { x: x1, y: y1 },
+            to: Point { x: x2, y: y2 },
+        } = data;
+
+        struct DispatchXs<F>(Coord, Coord, F);
+        impl<F: FnMut((Coord, Coord))> DispatchIterator for DispatchXs<F> {
+            fn run<I: IntoIterator<Item = Coord>>(self, i: I) {
+                dispatch_range(self.0, self.1, DispatchYs(i, self.2))
+            }
+        }
+
+        struct DispatchYs<I, F>(I, F);
+        impl<I2: IntoIterator<Item = Coord>, F: FnMut((Coord, Coord))> DispatchIterator
+            for DispatchYs<I2, F>
+        {
+            fn run<I: IntoIterator<Item = Coord>>(self, i: I) {
+                self.0.into_iter().zip(i).for_each(self.1)
+            }
+        }
+
+        if allow_diagonal || (x1 == x2 || y1 == y2) {
+            // a single point would otherwise repeat forever
+            if (x1, y1) == (x2, y2) {
+                return [(x1, y1)].into_iter().for_each(f);
+            }
+            dispatch_range(x1, x2, DispatchXs(y1, y2, f));
+        }
+    }
+
+    /// The original rasterising implementation.
+    fn rasterised_overlaps(data: &[Data], allow_diagonal: bool) -> Overlaps {
+        let mut counts = HashMap::default();
+        for d in data {
+            iter_lines(d, allow_diagonal, |(x, y)| {
+                *counts.entry((x, y)).or_insert(0) += 1;
+            });
+        }
+
+        Overlaps {
+            points: counts.values().filter(|&&count| count >= 2).count() as u128,
+            max_depth: counts.values().copied().max().unwrap_or(0),
+        }
+    }
+
+    #[test]
+    fn matches_rasterised() {
+        let mut rng = XorShift(0x5eed_0005);
+        for _ in 0..200 {
+            let data = (0..rng.below(40))
+                .map(|_| {
+                    let mut coord = || rng.below(30) as Coord - 10;
+                    let (x, y, len) = (coord(), coord(), coord());
+                    match rng.below(5) {
+                        0 => Data::new(x, y, x + len, y),
+                        1 => Data::new(x, y, x, y + len),
+                        2 => Data::new(x, y, x + len, y + len),
+                        3 => Data::new(x, y, x + len, y - len),
+                        _ => Data::new(x, y, x, y),
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            for allow_diagonal in [false, true] {
+                assert_eq!(
+                    super::overlaps(&data, allow_diagonal),
+                    rasterised_overlaps(&data, allow_diagonal),
+                    "{:?}",
+                    data
+                );
+            }
+        }
+    }
 }
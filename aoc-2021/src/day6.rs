@@ -8,27 +8,189 @@ use itertools::Itertools;
 
 use anyhow::bail;
 use aoc_utils::libs::*;
+use aoc_utils::linalg::{Element, Matrix};
 use aoc_utils::try_from_lines;
 
 type Data = u8;
 
 #[aoc_generator(day6)]
 pub fn generate(s: &str) -> Vec<Data> {
-    s.split(',').map(str::parse).try_collect().unwrap()
+    s.trim()
+        .split(',')
+        .map(|n| n.trim().parse())
+        .try_collect()
+        .unwrap()
 }
 
-fn population_after(days: usize, data: &[Data]) -> u64 {
-    let mut population = [0; 9];
-    for &d in data {
-        population[d as usize] += 1;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Model {
+    /// timer of a fish right after it spawned
+    pub reset: u8,
+    /// timer of a newborn fish
+    pub newborn: u8,
+    /// days a newborn waits before its timer starts counting down
+    pub maturity: u32,
+    /// fish die once they have lived this many days, counting initial fish as newborns
+    pub lifespan: Option<u32>,
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Model {
+            reset: 6,
+            newborn: 8,
+            maturity: 0,
+            lifespan: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    timer: u8,
+    /// days left before the timer starts counting down
+    frozen: u32,
+    /// only tracked if the fish can die
+    age: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    model: Model,
+    population: HashMap<State, u64>,
+    timers: usize,
+}
+
+impl Simulation {
+    pub fn new(model: Model, data: &[Data]) -> Self {
+        let mut population = HashMap::default();
+        for &timer in data {
+            let state = State {
+                timer,
+                frozen: 0,
+                age: 0,
+            };
+            *population.entry(state).or_default() += 1;
+        }
+        let timers = data
+            .iter()
+            .chain([&model.reset, &model.newborn])
+            .max()
+            .map_or(0, |&max| max as usize + 1);
+
+        Simulation {
+            model,
+            population,
+            timers,
+        }
+    }
+
+    pub fn step(&mut self) {
+        let model = self.model;
+        let mut next = HashMap::default();
+        let mut add = |state, count| *next.entry(state).or_insert(0) += count;
+
+        for (&State { timer, frozen, age }, &count) in &self.population {
+            let age = match model.lifespan {
+                Some(lifespan) if age + 1 >= lifespan => continue,
+                Some(_) => age + 1,
+                None => 0,
+            };
+
+            if frozen > 0 {
+                add(
+                    State {
+                        timer,
+                        frozen: frozen - 1,
+                        age,
+                    },
+                    count,
+                );
+            } else if timer > 0 {
+                add(
+                    State {
+                        timer: timer - 1,
+                        frozen,
+                        age,
+                    },
+                    count,
+                );
+            } else {
+                add(
+                    State {
+                        timer: model.reset,
+                        frozen,
+                        age,
+                    },
+                    count,
+                );
+                add(
+                    State {
+                        timer: model.newborn,
+                        frozen: model.maturity,
+                        age: 0,
+                    },
+                    count,
+                );
+            }
+        }
+
+        self.population = next;
+    }
+
+    /// Number of fish with each timer value.
+    pub fn histogram(&self) -> Vec<u64> {
+        let mut histogram = vec![0; self.timers];
+        for (state, &count) in &self.population {
+            histogram[state.timer as usize] += count;
+        }
+        histogram
+    }
+
+    pub fn total(&self) -> u64 {
+        self.population.values().sum()
     }
+}
 
+/// Histograms of timer values for each day from `0` to `days`.
+pub fn histograms(model: Model, data: &[Data], days: usize) -> Vec<Vec<u64>> {
+    let mut simulation = Simulation::new(model, data);
+    let mut histograms = vec![simulation.histogram()];
     for _ in 0..days {
-        population.rotate_left(1);
-        population[6] += population[8];
+        simulation.step();
+        histograms.push(simulation.histogram());
+    }
+    histograms
+}
+
+fn population_after(days: usize, data: &[Data]) -> u64 {
+    let mut simulation = Simulation::new(Model::default(), data);
+    for _ in 0..days {
+        simulation.step();
+    }
+
+    simulation.total()
+}
+
+/// Same as [`population_after`], in `O(log days)`.
+pub fn population_after_pow<T: Element>(days: u64, data: &[Data]) -> T {
+    // initial timers above 8 are allowed and simply count down
+    let timers = data.iter().max().map_or(0, |&max| max as usize).max(8) + 1;
+    let mut population = vec![T::zero(); timers];
+    for &d in data {
+        population[d as usize] = population[d as usize].plus(&T::one());
     }
 
-    population.iter().sum()
+    // timers count down, with fish at 0 resetting to 6 and spawning a newborn at 8
+    let day = Matrix::from_fn(timers, |to, from| {
+        let spawned = from == 0 && (to == 6 || to == 8);
+        T::from_u64((to + 1 == from || spawned) as u64)
+    });
+
+    day.pow(days)
+        .apply(&population)
+        .iter()
+        .fold(T::zero(), |acc, v| acc.plus(v))
 }
 
 #[aoc(day6, part1)]
@@ -43,7 +205,9 @@ pub fn day6_part2(data: &[Data]) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Data, Model};
+    use aoc_utils::libs::num_bigint::BigUint;
+    use aoc_utils::linalg::Modular;
 
     const EXAMPLE_INPUT: &str = r#"3,4,3,1,2"#;
 
@@ -65,4 +229,121 @@ mod tests {
     fn day6_part2() {
         assert_eq!(super::day6_part2(&get_example_data()), 26984457539);
     }
+
+    #[test]
+    fn population_after_pow() {
+        let data = get_example_data();
+        for days in [0, 1, 18, 80, 256] {
+            assert_eq!(
+                super::population_after_pow::<u64>(days, &data),
+                super::population_after(days as usize, &data)
+            );
+        }
+
+        assert_eq!(
+            super::population_after_pow::<BigUint>(500, &data),
+            BigUint::from(super::population_after_pow::<u128>(500, &data))
+        );
+        assert_eq!(
+            super::population_after_pow::<Modular<1_000_000_007>>(256, &data),
+            Modular(26984457539 % 1_000_000_007)
+        );
+        assert_eq!(
+            BigUint::from(super::population_after_pow::<Modular<1_000_000_007>>(3000, &data).0),
+            super::population_after_pow::<BigUint>(3000, &data) % 1_000_000_007_u64
+        );
+
+        // timers modulo 10 are periodic, so a day-by-day simulation covers any day
+        let mut timers = [0_u64; 9];
+        for &d in &data {
+            timers[d as usize] += 1;
+        }
+        let initial = timers;
+        let day = |timers: &mut [u64; 9]| {
+            timers.rotate_left(1);
+            timers[6] = (timers[6] + timers[8]) % 10;
+        };
+        let mut period = 0_u64;
+        loop {
+            day(&mut timers);
+            period += 1;
+            if timers == initial {
+                break;
+            }
+        }
+        for _ in 0..1_000_000_000_000 % period {
+            day(&mut timers);
+        }
+        assert_eq!(
+            super::population_after_pow::<Modular<10>>(1_000_000_000_000, &data),
+            Modular(timers.iter().sum::<u64>() % 10)
+        );
+    }
+
+    #[test]
+    fn large_initial_timers() {
+        let data = [3, 12, 40, 0];
+        for days in [0, 1, 13, 100, 300] {
+            assert_eq!(
+                super::population_after_pow::<u64>(days, &data),
+                super::population_after(days as usize, &data)
+            );
+        }
+        assert_eq!(super::population_after_pow::<u64>(0, &[]), 0);
+    }
+
+    #[test]
+    fn generate_whitespace() {
+        assert_eq!(super::generate(" 3, 4,3,\n1 ,2\n"), get_example_data());
+    }
+
+    #[test]
+    fn histograms() {
+        let histograms = super::histograms(Model::default(), &get_example_data(), 18);
+        assert_eq!(histograms[0], [0, 1, 1, 2, 1, 0, 0, 0, 0]);
+        assert_eq!(histograms[1], [1, 1, 2, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(histograms[2], [1, 2, 1, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(histograms[18].iter().sum::<u64>(), 26);
+    }
+
+    #[test]
+    fn maturity() {
+        let model = Model {
+            maturity: 2,
+            ..Model::default()
+        };
+        let histograms = super::histograms(model, &[0], 9);
+        assert_eq!(histograms[3], [0, 0, 0, 0, 1, 0, 0, 0, 1]);
+        assert_eq!(histograms[4], [0, 0, 0, 1, 0, 0, 0, 1, 0]);
+        assert_eq!(histograms[9], [0, 0, 1, 0, 0, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn lifespan() {
+        let model = Model {
+            lifespan: Some(3),
+            ..Model::default()
+        };
+        let totals = super::histograms(model, &[0], 5)
+            .iter()
+            .map(|h| h.iter().sum::<u64>())
+            .collect::<Vec<_>>();
+        assert_eq!(totals, [1, 2, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn custom_timers() {
+        let model = Model {
+            reset: 1,
+            newborn: 1,
+            ..Model::default()
+        };
+        // the population doubles every other day
+        let mut simulation = super::Simulation::new(model, &[0]);
+        for _ in 0..10 {
+            simulation.step();
+        }
+        assert_eq!(simulation.total(), 32);
+        assert_eq!(simulation.histogram().len(), 2);
+    }
 }
@@ -1,5 +1,6 @@
 #![allow(unused_imports)]
 
+use std::cmp::Ordering;
 use std::convert::TryFrom;
 
 use anyhow::bail;
@@ -10,43 +11,100 @@ use itertools::Itertools;
 use aoc_utils::libs::*;
 use aoc_utils::try_from_lines;
 
-type Data = i16;
+type Data = i64;
+pub type Fuel = i128;
 
 #[aoc_generator(day7)]
 pub fn generate(s: &str) -> Vec<Data> {
-    s.split(',').map(str::parse).try_collect().unwrap()
+    s.trim().split(',').map(str::parse).try_collect().unwrap()
 }
 
-#[aoc(day7, part1)]
-pub fn day7_part1(data: &[Data]) -> i32 {
-    let (min, max) = data.iter().copied().minmax().into_option().unwrap();
-    let mut fuel = vec![0; (max - min) as usize];
-    for &d in data {
-        for (f, i) in fuel.iter_mut().zip(min..) {
-            *f += (d - i).abs() as i32;
-        }
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    pub position: Data,
+    pub fuel: Fuel,
+}
 
-    fuel.iter().copied().min().unwrap()
+pub fn linear(distance: u64) -> Fuel {
+    distance as Fuel
 }
 
-#[aoc(day7, part2)]
-pub fn day7_part2(data: &[Data]) -> i32 {
-    let (min, max) = data.iter().copied().minmax().into_option().unwrap();
-    let mut fuel = vec![0; (max - min) as usize];
-    for &d in data {
-        for (f, i) in fuel.iter_mut().zip(min..) {
-            let diff = (d - i).abs() as i32;
-            *f += diff * (diff + 1) / 2;
+pub fn triangular(distance: u64) -> Fuel {
+    let distance = distance as Fuel;
+    distance * (distance + 1) / 2
+}
+
+pub fn total_fuel(data: &[Data], position: Data, cost: impl Fn(u64) -> Fuel) -> Fuel {
+    data.iter().map(|&d| cost(d.abs_diff(position))).sum()
+}
+
+fn best_of(
+    data: &[Data],
+    positions: impl IntoIterator<Item = Data>,
+    cost: impl Fn(u64) -> Fuel,
+) -> Alignment {
+    positions
+        .into_iter()
+        .map(|position| Alignment {
+            position,
+            fuel: total_fuel(data, position, &cost),
+        })
+        .min_by_key(|alignment| alignment.fuel)
+        .expect("no crabs")
+}
+
+/// With linear cost the median is optimal.
+pub fn align_linear(data: &[Data]) -> Alignment {
+    let mut data = data.to_vec();
+    let mid = (data.len().checked_sub(1).expect("no crabs")) / 2;
+    let median = *data.select_nth_unstable(mid).1;
+    best_of(&data, [median], linear)
+}
+
+/// With triangular cost the optimum is within half a step of the mean.
+pub fn align_triangular(data: &[Data]) -> Alignment {
+    assert!(!data.is_empty(), "no crabs");
+    let sum: i128 = data.iter().map(|&d| d as i128).sum();
+    let mean = sum.div_euclid(data.len() as i128) as Data;
+    best_of(data, [mean, mean + 1], triangular)
+}
+
+/// Ternary search over positions, correct for any convex `cost`.
+pub fn align_convex(data: &[Data], cost: impl Fn(u64) -> Fuel) -> Alignment {
+    let (mut lo, mut hi) = data
+        .iter()
+        .copied()
+        .minmax()
+        .into_option()
+        .expect("no crabs");
+    let fuel = |position| total_fuel(data, position, &cost);
+    while hi.abs_diff(lo) > 2 {
+        let third = (hi.abs_diff(lo) / 3) as Data;
+        let (m1, m2) = (lo + third, hi - third);
+        match fuel(m1).cmp(&fuel(m2)) {
+            Ordering::Less => hi = m2 - 1,
+            Ordering::Greater => lo = m1 + 1,
+            Ordering::Equal => (lo, hi) = (m1, m2),
         }
     }
 
-    fuel.iter().copied().min().unwrap()
+    best_of(data, lo..=hi, &cost)
+}
+
+#[aoc(day7, part1)]
+pub fn day7_part1(data: &[Data]) -> Fuel {
+    align_linear(data).fuel
+}
+
+#[aoc(day7, part2)]
+pub fn day7_part2(data: &[Data]) -> Fuel {
+    align_triangular(data).fuel
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Alignment, Data, Fuel};
+    use aoc_utils::rng::XorShift;
 
     const EXAMPLE_INPUT: &str = r#"16,1,2,0,4,2,7,1,2,14"#;
 
@@ -68,4 +126,68 @@ mod tests {
     fn day7_part2() {
         assert_eq!(super::day7_part2(&get_example_data()), 168);
     }
+
+    #[test]
+    fn best_positions() {
+        let data = get_example_data();
+        let expected = Alignment {
+            position: 2,
+            fuel: 37,
+        };
+        assert_eq!(super::align_linear(&data), expected);
+        assert_eq!(super::align_convex(&data, super::linear), expected);
+
+        let expected = Alignment {
+            position: 5,
+            fuel: 168,
+        };
+        assert_eq!(super::align_triangular(&data), expected);
+        assert_eq!(super::align_convex(&data, super::triangular), expected);
+    }
+
+    #[test]
+    fn wide_positions() {
+        let data = [-(1 << 62), 1 << 62, 0, 5];
+        assert_eq!(super::align_linear(&data).fuel, (1 << 63) + 5);
+        assert_eq!(
+            super::align_convex(&data, super::linear).fuel,
+            super::align_linear(&data).fuel
+        );
+
+        let data = [-(1 << 60), 1 << 60, 0, 5];
+        assert_eq!(
+            super::align_convex(&data, super::triangular),
+            super::align_triangular(&data)
+        );
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let costs: [fn(u64) -> Fuel; 3] = [super::linear, super::triangular, |d| {
+            let d = d as Fuel;
+            d * d * d + 5 * d
+        }];
+
+        let mut rng = XorShift(0x5eed_0007);
+        for _ in 0..300 {
+            let data = (0..=rng.below(20))
+                .map(|_| rng.below(100) as Data - 50)
+                .collect::<Vec<_>>();
+            for cost in costs {
+                let brute_force = (-50..50)
+                    .map(|position| super::total_fuel(&data, position, cost))
+                    .min()
+                    .unwrap();
+                assert_eq!(super::align_convex(&data, cost).fuel, brute_force);
+            }
+            assert_eq!(
+                super::align_linear(&data).fuel,
+                super::align_convex(&data, super::linear).fuel
+            );
+            assert_eq!(
+                super::align_triangular(&data).fuel,
+                super::align_convex(&data, super::triangular).fuel
+            );
+        }
+    }
 }
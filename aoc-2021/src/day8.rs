@@ -89,87 +89,205 @@ const LOOKUP: [[bool; 7]; 10] = [
     [T, T, T, T, F, T, T], // 9
 ];
 
-#[allow(clippy::many_single_char_names)]
-#[aoc(day8, part2)]
-pub fn day8_part2(data: &[Data<'_>]) -> i32 {
-    let mut rest = Vec::with_capacity(6);
-    let mut sum = 0;
-
-    for data in data {
-        let (mut d1, mut d4, mut d7, mut d8) = ("", "", "", "");
-        rest.clear();
-        for display in data.input {
-            match display.len() {
-                2 => d1 = display,
-                4 => d4 = display,
-                3 => d7 = display,
-                7 => d8 = display,
-                _ => rest.push(display),
-            }
+/// Glyphs as bitmasks, bit `i` set if segment `i` is lit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlyphTable {
+    segments: usize,
+    glyphs: Vec<u32>,
+}
+
+impl GlyphTable {
+    pub fn new(segments: usize, glyphs: Vec<u32>) -> Self {
+        assert!(segments <= 26, "wires are named by lowercase letters");
+        GlyphTable { segments, glyphs }
+    }
+
+    pub fn from_lookup<const N: usize>(lookup: &[[bool; N]]) -> Self {
+        let glyphs = lookup
+            .iter()
+            .map(|segments| {
+                segments
+                    .iter()
+                    .rev()
+                    .fold(0, |mask, &lit| mask << 1 | lit as u32)
+            })
+            .collect();
+        Self::new(N, glyphs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SolveError {
+    /// no wiring explains all observations
+    Inconsistent,
+    /// more than one wiring explains all observations
+    Ambiguous,
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::Inconsistent => write!(f, "no wiring matches the observations"),
+            SolveError::Ambiguous => write!(f, "more than one wiring matches the observations"),
         }
-        debug_assert_ne!(d1, "");
-        debug_assert_ne!(d4, "");
-        debug_assert_ne!(d7, "");
-        debug_assert_ne!(d8, "");
-
-        let a = d7.bytes().find(|ch| !d1.as_bytes().contains(ch)).unwrap();
-        let g = (b'a'..=b'g')
-            .filter(|&ch| ch != a)
-            .find(|ch| rest.iter().all(|disp| disp.as_bytes().contains(ch)))
-            .unwrap();
-        let bd: [u8; 2] = collect_arr(d4.bytes().filter(|ch| !d1.as_bytes().contains(ch)));
-        let d = bd
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// Segment driven by each wire.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Wiring {
+    segment_of: Vec<usize>,
+}
+
+impl Wiring {
+    /// Index of the glyph shown by a scrambled pattern, if any.
+    pub fn decode(&self, table: &GlyphTable, pattern: &str) -> Option<usize> {
+        let wires = wire_mask(table, pattern)?;
+        let segments = (0..table.segments)
+            .filter(|&wire| wires & 1 << wire != 0)
+            .fold(0, |mask, wire| mask | 1 << self.segment_of[wire]);
+        table.glyphs.iter().position(|&glyph| glyph == segments)
+    }
+}
+
+fn wire_mask(table: &GlyphTable, pattern: &str) -> Option<u32> {
+    pattern.bytes().try_fold(0, |mask, ch| {
+        let wire = ch
+            .checked_sub(b'a')
+            .filter(|&w| (w as usize) < table.segments)?;
+        Some(mask | 1 << wire)
+    })
+}
+
+struct Solver<'t> {
+    table: &'t GlyphTable,
+    observations: Vec<u32>,
+    /// wires in the order they're assigned
+    order: Vec<usize>,
+    candidates: Vec<u32>,
+    segment_of: Vec<usize>,
+    solutions: Vec<Wiring>,
+}
+
+impl Solver<'_> {
+    fn glyphs_like(&self, observation: u32) -> impl Iterator<Item = u32> + '_ {
+        self.table
+            .glyphs
             .iter()
             .copied()
-            .find(|ch| {
-                rest.iter()
-                    .filter(|disp| !disp.as_bytes().contains(ch))
-                    .count()
-                    == 1
-            })
-            .unwrap();
-        debug_assert!(bd.contains(&d));
-        let b = bd.iter().copied().find(|&ch| ch != d).unwrap();
+            .filter(move |glyph| glyph.count_ones() == observation.count_ones())
+    }
 
-        let (mut c, mut e, mut f) = (0, 0, 0);
-        for candidate in (b'a'..=b'g').filter(|ch| ![a, b, d, g].contains(ch)) {
-            let missing_count = rest
-                .iter()
-                .filter(|disp| !disp.as_bytes().contains(&candidate))
-                .count();
-            match missing_count {
-                1 => f = candidate,
-                2 => c = candidate,
-                3 => e = candidate,
-                _ => unreachable!(),
+    /// Narrows down the segments each wire can drive: a wire lit in an observation must
+    /// drive a segment of some glyph of the same size, an unlit one a segment outside it.
+    fn propagate(&mut self) {
+        let all = (1 << self.table.segments) - 1;
+        for &observation in &self.observations {
+            let (lit, unlit) = self
+                .glyphs_like(observation)
+                .fold((0, 0), |(lit, unlit), glyph| {
+                    (lit | glyph, unlit | !glyph & all)
+                });
+            for (wire, candidates) in self.candidates.iter_mut().enumerate() {
+                *candidates &= if observation & 1 << wire != 0 {
+                    lit
+                } else {
+                    unlit
+                };
             }
         }
-        debug_assert_ne!(c, 0);
-        debug_assert_ne!(e, 0);
-        debug_assert_ne!(f, 0);
+    }
 
-        let segment_letters = [a, b, c, d, e, f, g];
+    /// Whether every observation can still match some glyph given the first `assigned`
+    /// wires of `order`.
+    fn feasible(&self, assigned: usize) -> bool {
+        self.observations.iter().all(|&observation| {
+            let (mut lit, mut unlit) = (0, 0);
+            for &wire in &self.order[..assigned] {
+                if observation & 1 << wire != 0 {
+                    lit |= 1 << self.segment_of[wire];
+                } else {
+                    unlit |= 1 << self.segment_of[wire];
+                }
+            }
+            self.glyphs_like(observation)
+                .any(|glyph| lit & !glyph == 0 && unlit & glyph == 0)
+        })
+    }
 
-        let mut number = 0;
-        for output in data.output.iter().copied() {
-            let mut segments = [false; 7];
-            for ch in output.bytes() {
-                segments[segment_letters.iter().position(|&chh| ch == chh).unwrap()] = true;
+    fn search(&mut self, assigned: usize, used: u32) {
+        if self.solutions.len() > 1 || !self.feasible(assigned) {
+            return;
+        }
+        let wire = match self.order.get(assigned) {
+            Some(&wire) => wire,
+            None => {
+                self.solutions.push(Wiring {
+                    segment_of: self.segment_of.clone(),
+                });
+                return;
             }
+        };
 
-            let value = LOOKUP.iter().position(|s| s == &segments).unwrap();
-            number = number * 10 + value as i32;
+        let mut free = self.candidates[wire] & !used;
+        while free != 0 {
+            let segment = free.trailing_zeros() as usize;
+            free &= free - 1;
+            self.segment_of[wire] = segment;
+            self.search(assigned + 1, used | 1 << segment);
         }
+    }
+}
+
+/// Finds the only wiring under which every scrambled observation shows a glyph of `table`.
+pub fn solve(table: &GlyphTable, observations: &[&str]) -> Result<Wiring, SolveError> {
+    let observations = observations
+        .iter()
+        .map(|pattern| wire_mask(table, pattern))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(SolveError::Inconsistent)?;
 
-        sum += number;
+    let mut solver = Solver {
+        table,
+        observations,
+        order: Vec::new(),
+        candidates: vec![(1 << table.segments) - 1; table.segments],
+        segment_of: vec![0; table.segments],
+        solutions: Vec::new(),
+    };
+    solver.propagate();
+    solver.order = (0..table.segments)
+        .sorted_by_key(|&wire| solver.candidates[wire].count_ones())
+        .collect();
+    solver.search(0, 0);
+
+    match solver.solutions.len() {
+        0 => Err(SolveError::Inconsistent),
+        1 => Ok(solver.solutions.pop().unwrap()),
+        _ => Err(SolveError::Ambiguous),
     }
+}
 
-    sum
+#[aoc(day8, part2)]
+pub fn day8_part2(data: &[Data<'_>]) -> i32 {
+    let table = GlyphTable::from_lookup(&LOOKUP);
+
+    data.iter()
+        .map(|data| {
+            let wiring = solve(&table, &data.input).expect("couldn't solve wiring");
+            data.output.iter().fold(0, |number, output| {
+                let digit = wiring.decode(&table, output).expect("unknown digit");
+                number * 10 + digit as i32
+            })
+        })
+        .sum()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Data, GlyphTable, SolveError, LOOKUP};
 
     const EXAMPLE_INPUT: &str = "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb |\
 fdgacbe cefdb cefbgd gcbe
@@ -281,4 +399,79 @@ fgae cfgab fg bagce";
     fn day8_part2() {
         assert_eq!(super::day8_part2(&get_example_data()), 61229);
     }
+
+    #[test]
+    fn decode() {
+        let table = GlyphTable::from_lookup(&LOOKUP);
+        let observations = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab"
+            .split(' ')
+            .collect::<Vec<_>>();
+        let wiring = super::solve(&table, &observations).unwrap();
+        let digits = ["cdfeb", "fcadb", "cdfeb", "cdbaf"].map(|p| wiring.decode(&table, p));
+        assert_eq!(digits, [Some(5), Some(3), Some(5), Some(3)]);
+        assert_eq!(wiring.decode(&table, "abcd"), None);
+        assert_eq!(wiring.decode(&table, "xyz"), None);
+    }
+
+    #[test]
+    fn errors() {
+        let table = GlyphTable::from_lookup(&LOOKUP);
+        assert_eq!(super::solve(&table, &["ab"]), Err(SolveError::Ambiguous));
+        assert_eq!(
+            super::solve(&table, &["ab", "cd"]),
+            Err(SolveError::Inconsistent)
+        );
+        assert_eq!(
+            super::solve(&table, &["abcz"]),
+            Err(SolveError::Inconsistent)
+        );
+    }
+
+    /// Shows every glyph of `table` through `wires`, a permutation of segment indices.
+    fn scramble(table: &GlyphTable, wires: &[usize]) -> Vec<String> {
+        table
+            .glyphs
+            .iter()
+            .map(|&glyph| {
+                (0..wires.len())
+                    .filter(|&wire| glyph & 1 << wires[wire] != 0)
+                    .map(|wire| (b'a' + wire as u8) as char)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arbitrary_tables() {
+        let wires = [3, 6, 0, 5, 1, 2, 4];
+
+        // segments a and g are lit in exactly the same glyphs
+        let table = GlyphTable::from_lookup(&[0, 2, 3, 5, 6, 9].map(|d| LOOKUP[d]));
+        let observations = scramble(&table, &wires);
+        let observations = observations.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(
+            super::solve(&table, &observations),
+            Err(SolveError::Ambiguous)
+        );
+
+        // without 1 and 8, and only 7 telling segments a and g apart
+        let table = GlyphTable::from_lookup(&[0, 2, 3, 4, 5, 6, 7, 9].map(|d| LOOKUP[d]));
+        let observations = scramble(&table, &wires);
+        let observations = observations.iter().map(String::as_str).collect::<Vec<_>>();
+        let wiring = super::solve(&table, &observations).unwrap();
+        for (i, observation) in observations.iter().enumerate() {
+            assert_eq!(wiring.decode(&table, observation), Some(i));
+        }
+
+        // an extra segment lit only in a new glyph
+        let mut table = GlyphTable::from_lookup(&LOOKUP);
+        table.segments += 1;
+        table.glyphs.push(0b1000_0001);
+        let wires = [7, 3, 6, 0, 5, 1, 2, 4];
+        let observations = scramble(&table, &wires);
+        let observations = observations.iter().map(String::as_str).collect::<Vec<_>>();
+        let wiring = super::solve(&table, &observations).unwrap();
+        assert_eq!(wiring.decode(&table, observations[10]), Some(10));
+        assert_eq!(wiring.decode(&table, observations[4]), Some(4));
+    }
 }
@@ -20,130 +20,152 @@ pub fn generate<'input>(s: &'input str) -> Vec<Data<'input>> {
     s.lines().collect()
 }
 
-const PADDING: &[u8] = &[u8::MAX; 200];
+pub type Position = (usize, usize);
 
-#[aoc(day9, part1)]
-pub fn day9_part1(data: &[Data<'_>]) -> i32 {
-    [PADDING]
-        .into_iter()
-        .chain(data.iter().map(|s| s.as_bytes()))
-        .chain([PADDING])
-        .tuple_windows()
-        .map(|(win0, win1, win2)| {
-            izip!(
-                win0.iter().copied(),
-                [u8::MAX]
-                    .into_iter()
-                    .chain(win1.iter().copied())
-                    .chain([u8::MAX])
-                    .tuple_windows(),
-                win2.iter().copied(),
-            )
-            .filter(|&(a, (x, y, z), c)| y < x && y < z && y < a && y < c)
-            .map(|(_, b, _)| (b.1 - b'0') + 1)
-            .sum::<u8>() as i32
-        })
-        .sum()
-}
-
-#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq)]
-enum State {
-    Initial,
-    Visited(u16),
-    Wall,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    heights: Vec<Vec<u8>>,
 }
 
-fn _print_state<I>(state: &[I])
-where
-    for<'a> &'a I: IntoIterator<Item = &'a State>,
-{
-    for row in state {
-        for s in row {
-            match s {
-                State::Initial => print!("."),
-                State::Visited(x) => print!("{}", x),
-                State::Wall => print!("#"),
-            }
+impl Heightmap {
+    pub fn new(data: &[Data<'_>]) -> anyhow::Result<Self> {
+        let heights = data
+            .iter()
+            .enumerate()
+            .map(|(y, line)| {
+                line.bytes()
+                    .map(|ch| match ch {
+                        b'0'..=b'9' => Ok(ch - b'0'),
+                        _ => bail!("invalid height {:?} in row {}", ch as char, y + 1),
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let width = heights.first().map_or(0, Vec::len);
+        if let Some(y) = heights.iter().position(|row| row.len() != width) {
+            bail!(
+                "row {} has width {}, expected {}",
+                y + 1,
+                heights[y].len(),
+                width
+            );
         }
-        println!()
+
+        Ok(Heightmap {
+            width,
+            height: heights.len(),
+            heights,
+        })
     }
-}
 
-#[aoc(day9, part2)]
-pub fn day9_part2(data: &[Data<'_>]) -> i32 {
-    let width = data[0].len();
-    let height = data.len();
-    let mut state = data
-        .iter()
-        .map(|_| vec![State::Initial; width])
-        .collect_vec();
+    pub fn get(&self, (x, y): Position) -> u8 {
+        self.heights[y][x]
+    }
 
-    let mut queue = VecDeque::with_capacity(32);
+    /// Positions strictly lower than all of their neighbours, in row-major order.
+    pub fn low_points(&self) -> Vec<Position> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&pos| {
+                let mut lowest = true;
+                for_neighbours_4(pos, (self.width, self.height), |next| {
+                    lowest &= self.get(pos) < self.get(next);
+                });
+                lowest
+            })
+            .collect()
+    }
 
-    queue.push_back((0, 0));
+    /// Regions of positions lower than `wall` connected horizontally or vertically.
+    pub fn basins(&self, wall: u8) -> Basins {
+        let mut labels = vec![vec![None; self.width]; self.height];
+        let mut basins = Vec::new();
+        let mut queue = VecDeque::new();
 
-    let mut basins = vec![];
-    let mut visited_rows = 0;
+        for start in (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y))) {
+            if self.get(start) >= wall || labels[start.1][start.0].is_some() {
+                continue;
+            }
 
-    loop {
-        if !matches!(basins.last(), Some(0)) {
-            basins.push(0);
-        }
-        let current_basin_index = basins.len();
-        let current_basin = basins.last_mut().unwrap();
-        while let Some(current) = queue.pop_front() {
-            let (x, y) = current;
-            let current_state = &mut state[y][x];
-            let current_depth = data[y].as_bytes()[x] - b'0';
-            match (*current_state, current_depth) {
-                (State::Initial, 9) => {
-                    *current_state = State::Wall;
-                    continue;
-                }
-                (State::Initial, _) => {
-                    *current_basin += 1;
-                    *current_state = State::Visited(current_basin_index as _);
-                }
-                (State::Visited(b), _) => {
-                    debug_assert_eq!(b as usize, current_basin_index);
-                    continue;
-                }
-                (State::Wall, 9) => {
-                    continue;
-                }
-                _ => unreachable!(),
+            let label = basins.len();
+            let mut cells = vec![];
+            labels[start.1][start.0] = Some(label);
+            queue.push_back(start);
+            while let Some(current) = queue.pop_front() {
+                cells.push(current);
+                for_neighbours_4(current, (self.width, self.height), |(x, y)| {
+                    if self.get((x, y)) < wall && labels[y][x].is_none() {
+                        labels[y][x] = Some(label);
+                        queue.push_back((x, y));
+                    }
+                });
             }
-            for_neighbours_4(current, (width, height), |next| {
-                queue.push_back(next);
-            });
-        }
 
-        // _print_state(&state);
-
-        if let Some(unvisited_position) =
-            state[visited_rows..]
-                .iter()
-                .zip(visited_rows..)
-                .find_map(|(row, y)| {
-                    row.iter()
-                        .position(|s| s == &State::Initial)
-                        .map(|x| (x, y))
-                })
-        {
-            visited_rows = unvisited_position.1;
-            queue.push_back(unvisited_position);
-        } else {
-            break;
+            cells.sort_unstable_by_key(|&(x, y)| (y, x));
+            basins.push(Basin { cells });
         }
+
+        Basins { basins, labels }
     }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basin {
+    /// in row-major order
+    pub cells: Vec<Position>,
+}
+
+impl Basin {
+    pub fn size(&self) -> usize {
+        self.cells.len()
+    }
+}
 
-    basins.sort_unstable();
-    basins.iter().rev().take(3).product()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basins {
+    /// ordered by their first cell in row-major order
+    pub basins: Vec<Basin>,
+    /// index of the basin containing each position, `None` for walls
+    pub labels: Vec<Vec<Option<usize>>>,
+}
+
+impl Basins {
+    pub fn label(&self, (x, y): Position) -> Option<usize> {
+        self.labels[y][x]
+    }
+}
+
+const WALL: u8 = 9;
+
+#[aoc(day9, part1)]
+pub fn day9_part1(data: &[Data<'_>]) -> u32 {
+    let heightmap = Heightmap::new(data).expect("invalid heightmap");
+    heightmap
+        .low_points()
+        .into_iter()
+        .map(|pos| heightmap.get(pos) as u32 + 1)
+        .sum()
+}
+
+#[aoc(day9, part2)]
+pub fn day9_part2(data: &[Data<'_>]) -> usize {
+    let heightmap = Heightmap::new(data).expect("invalid heightmap");
+    heightmap
+        .basins(WALL)
+        .basins
+        .iter()
+        .map(Basin::size)
+        .sorted_by(|a, b| b.cmp(a))
+        .take(3)
+        .product()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Data;
+    use super::{Data, Heightmap};
 
     const EXAMPLE_INPUT: &str = r#"2199943210
 3987894921
@@ -175,4 +197,44 @@ mod tests {
     fn day9_part2() {
         assert_eq!(super::day9_part2(&get_example_data()), 1134);
     }
+
+    #[test]
+    fn low_points() {
+        let heightmap = Heightmap::new(&get_example_data()).unwrap();
+        assert_eq!(heightmap.low_points(), [(1, 0), (9, 0), (2, 2), (6, 4)]);
+    }
+
+    #[test]
+    fn basins() {
+        let heightmap = Heightmap::new(&get_example_data()).unwrap();
+        let basins = heightmap.basins(9);
+        let sizes = basins.basins.iter().map(|b| b.size()).collect::<Vec<_>>();
+        assert_eq!(sizes, [3, 9, 14, 9]);
+        assert_eq!(basins.basins[0].cells, [(0, 0), (1, 0), (0, 1)]);
+        for (label, &low_point) in heightmap.low_points().iter().enumerate() {
+            assert_eq!(basins.label(low_point), Some(label));
+        }
+        assert_eq!(basins.label((2, 0)), None);
+
+        // lowering the walls shrinks the top-right basin
+        let basins = heightmap.basins(4);
+        assert_eq!(basins.label((9, 0)), Some(1));
+        assert_eq!(basins.label((5, 0)), None);
+        assert_eq!(
+            basins.basins[1].cells,
+            [(6, 0), (7, 0), (8, 0), (9, 0), (8, 1), (9, 1), (9, 2)]
+        );
+    }
+
+    #[test]
+    fn wide_maps() {
+        let row = "9".repeat(299) + "0";
+        let data = [row.as_str(), row.as_str()];
+        assert_eq!(super::day9_part1(&data), 0);
+        assert_eq!(super::day9_part2(&data), 2);
+        let row = "9".repeat(299) + "1";
+        assert_eq!(super::day9_part1(&[row.as_str()]), 2);
+        assert!(Heightmap::new(&["12", "1"]).is_err());
+        assert!(Heightmap::new(&["1a"]).is_err());
+    }
 }
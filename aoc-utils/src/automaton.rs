@@ -0,0 +1,408 @@
+use std::hash::Hash;
+use std::ops::ControlFlow;
+
+use crate::grids::Grid;
+use crate::libs::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Neighbourhood {
+    /// the 4 orthogonal neighbours
+    VonNeumann,
+    /// the 8 orthogonal and diagonal neighbours
+    Moore,
+    /// arbitrary `(dx, dy)` offsets
+    Custom(&'static [(isize, isize)]),
+}
+
+impl Neighbourhood {
+    pub fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Neighbourhood::VonNeumann => &[(1, 0), (0, 1), (-1, 0), (0, -1)],
+            Neighbourhood::Moore => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+            Neighbourhood::Custom(offsets) => offsets,
+        }
+    }
+}
+
+/// What lies beyond the edges of a [`Grid`]. Unbounded space is handled by
+/// [`SparseAutomaton`] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Boundary {
+    /// cells outside the grid don't exist
+    Bounded,
+    /// edges wrap around to the opposite side
+    Toroidal,
+}
+
+impl Boundary {
+    fn resolve(
+        self,
+        (x, y): (usize, usize),
+        (dx, dy): (isize, isize),
+        (width, height): (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let (x, y) = (x as isize + dx, y as isize + dy);
+        let (width, height) = (width as isize, height as isize);
+        match self {
+            Boundary::Bounded => ((0..width).contains(&x) && (0..height).contains(&y))
+                .then_some((x as usize, y as usize)),
+            Boundary::Toroidal => {
+                Some((x.rem_euclid(width) as usize, y.rem_euclid(height) as usize))
+            }
+        }
+    }
+}
+
+/// Rule where cells firing during a step affect their neighbours within the same step.
+pub trait CascadeRule<T> {
+    /// Updates every cell at the start of a step, returning whether it fires.
+    fn charge(&self, cell: &mut T) -> bool;
+    /// Updates a cell next to a firing one, returning whether it fires as a result.
+    /// Never called for cells that already fired in this step.
+    fn propagate(&self, cell: &mut T) -> bool;
+    /// Updates every cell that fired, at the end of a step.
+    fn settle(&self, cell: &mut T);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridAutomaton<T> {
+    grid: Grid<T>,
+    neighbourhood: Neighbourhood,
+    boundary: Boundary,
+    generation: u64,
+}
+
+impl<T> GridAutomaton<T> {
+    pub fn new(grid: Grid<T>, neighbourhood: Neighbourhood, boundary: Boundary) -> Self {
+        GridAutomaton {
+            grid,
+            neighbourhood,
+            boundary,
+            generation: 0,
+        }
+    }
+
+    pub fn grid(&self) -> &Grid<T> {
+        &self.grid
+    }
+
+    /// Number of steps taken so far.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn neighbours(&self, pos: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let size = (self.grid.width(), self.grid.height());
+        self.neighbourhood
+            .offsets()
+            .iter()
+            .filter_map(move |&offset| self.boundary.resolve(pos, offset, size))
+    }
+
+    /// Replaces every cell with `rule(cell, neighbours)`, all computed from the previous
+    /// generation.
+    pub fn step_sync(&mut self, rule: impl Fn(&T, &[&T]) -> T) {
+        let mut neighbours = Vec::with_capacity(self.neighbourhood.offsets().len());
+        let cells = self
+            .grid
+            .positions()
+            .map(|pos| {
+                neighbours.clear();
+                neighbours.extend(self.neighbours(pos).map(|n| &self.grid[n]));
+                rule(&self.grid[pos], &neighbours)
+            })
+            .collect();
+
+        self.grid = Grid::from_cells(self.grid.width(), self.grid.height(), cells);
+        self.generation += 1;
+    }
+
+    /// Runs a step of `rule`, returning the positions of cells that fired in the order
+    /// they did.
+    pub fn step_cascade(&mut self, rule: &impl CascadeRule<T>) -> Vec<(usize, usize)> {
+        let mut fired = Grid::from_cells(
+            self.grid.width(),
+            self.grid.height(),
+            vec![false; self.grid.len()],
+        );
+        let mut order = Vec::new();
+        for pos in self.grid.positions() {
+            if rule.charge(&mut self.grid[pos]) {
+                fired[pos] = true;
+                order.push(pos);
+            }
+        }
+
+        let mut next = 0;
+        while let Some(&pos) = order.get(next) {
+            next += 1;
+            let neighbours = self.neighbours(pos).collect::<Vec<_>>();
+            for n in neighbours {
+                if !fired[n] && rule.propagate(&mut self.grid[n]) {
+                    fired[n] = true;
+                    order.push(n);
+                }
+            }
+        }
+
+        for &pos in &order {
+            rule.settle(&mut self.grid[pos]);
+        }
+        self.generation += 1;
+        order
+    }
+
+    /// Runs synchronous steps until `callback`, called after each one, breaks.
+    pub fn run_sync<B>(
+        &mut self,
+        rule: impl Fn(&T, &[&T]) -> T,
+        mut callback: impl FnMut(&Self) -> ControlFlow<B>,
+    ) -> B {
+        loop {
+            self.step_sync(&rule);
+            if let ControlFlow::Break(b) = callback(self) {
+                return b;
+            }
+        }
+    }
+
+    /// Runs cascading steps until `callback`, called with the cells fired in each one,
+    /// breaks.
+    pub fn run_cascade<B>(
+        &mut self,
+        rule: &impl CascadeRule<T>,
+        mut callback: impl FnMut(&Self, &[(usize, usize)]) -> ControlFlow<B>,
+    ) -> B {
+        loop {
+            let fired = self.step_cascade(rule);
+            if let ControlFlow::Break(b) = callback(self, &fired) {
+                return b;
+            }
+        }
+    }
+}
+
+pub type Point = (i64, i64);
+
+/// Two-state automaton on an unbounded plane, storing only the live cells.
+#[derive(Debug, Clone)]
+pub struct SparseAutomaton {
+    live: HashSet<Point>,
+    neighbourhood: Neighbourhood,
+    generation: u64,
+}
+
+impl SparseAutomaton {
+    pub fn new(live: impl IntoIterator<Item = Point>, neighbourhood: Neighbourhood) -> Self {
+        SparseAutomaton {
+            live: live.into_iter().collect(),
+            neighbourhood,
+            generation: 0,
+        }
+    }
+
+    pub fn live(&self) -> &HashSet<Point> {
+        &self.live
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Live cells sorted by row, then column, e.g. for comparing states.
+    pub fn sorted_live(&self) -> Vec<Point> {
+        let mut live = self.live.iter().copied().collect::<Vec<_>>();
+        live.sort_unstable_by_key(|&(x, y)| (y, x));
+        live
+    }
+
+    /// Each cell lives in the next generation if `rule(alive, live_neighbours)`. Dead cells
+    /// without live neighbours must stay dead.
+    pub fn step(&mut self, rule: impl Fn(bool, usize) -> bool) {
+        let mut counts: HashMap<Point, usize> = HashMap::default();
+        for &(x, y) in &self.live {
+            counts.entry((x, y)).or_default();
+            for &(dx, dy) in self.neighbourhood.offsets() {
+                *counts.entry((x + dx as i64, y + dy as i64)).or_default() += 1;
+            }
+        }
+
+        self.live = counts
+            .into_iter()
+            .filter(|(pos, count)| rule(self.live.contains(pos), *count))
+            .map(|(pos, _)| pos)
+            .collect();
+        self.generation += 1;
+    }
+}
+
+/// Conway's Game of Life, for [`GridAutomaton::step_sync`] on `bool` cells.
+pub fn life(cell: &bool, neighbours: &[&bool]) -> bool {
+    life_sparse(*cell, neighbours.iter().filter(|&&&n| n).count())
+}
+
+/// Conway's Game of Life, for [`SparseAutomaton::step`].
+pub fn life_sparse(alive: bool, live_neighbours: usize) -> bool {
+    matches!((alive, live_neighbours), (true, 2) | (_, 3))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cycle {
+    /// first step of the repeating sequence of states
+    pub start: u64,
+    pub period: u64,
+}
+
+impl Cycle {
+    pub fn is_stable(&self) -> bool {
+        self.period == 1
+    }
+}
+
+/// Steps `state` until its `key` repeats, giving up after `max_steps` steps.
+pub fn find_cycle<S, K: Hash + Eq>(
+    state: &mut S,
+    mut step: impl FnMut(&mut S),
+    key: impl Fn(&S) -> K,
+    max_steps: u64,
+) -> Option<Cycle> {
+    let mut seen = HashMap::default();
+    seen.insert(key(state), 0);
+    for steps in 1..=max_steps {
+        step(state);
+        if let Some(start) = seen.insert(key(state), steps) {
+            return Some(Cycle {
+                start,
+                period: steps - start,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Grid<bool> {
+        Grid::from_rows(s.lines().map(|line| line.bytes().map(|b| b == b'#')))
+    }
+
+    #[test]
+    fn blinker() {
+        let grid = parse(".....\n..#..\n..#..\n..#..\n.....");
+        let mut automaton = GridAutomaton::new(grid, Neighbourhood::Moore, Boundary::Bounded);
+        automaton.step_sync(life);
+        assert_eq!(
+            automaton.grid(),
+            &parse(".....\n.....\n.###.\n.....\n.....")
+        );
+
+        let cycle = find_cycle(
+            &mut automaton,
+            |a| a.step_sync(life),
+            |a| a.grid().clone(),
+            10,
+        );
+        assert_eq!(
+            cycle,
+            Some(Cycle {
+                start: 0,
+                period: 2
+            })
+        );
+    }
+
+    #[test]
+    fn toroidal_glider() {
+        let grid = parse(".#....\n..#...\n###...\n......\n......\n......");
+        let mut automaton =
+            GridAutomaton::new(grid.clone(), Neighbourhood::Moore, Boundary::Toroidal);
+        let generation = automaton.run_sync(life, |a| {
+            if a.grid() == &grid {
+                ControlFlow::Break(a.generation())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(generation, 24);
+
+        // the same glider dies in the corner of a bounded grid
+        let mut automaton = GridAutomaton::new(grid, Neighbourhood::Moore, Boundary::Bounded);
+        let cycle = find_cycle(
+            &mut automaton,
+            |a| a.step_sync(life),
+            |a| a.grid().clone(),
+            100,
+        );
+        assert!(cycle.unwrap().is_stable());
+    }
+
+    #[test]
+    fn sparse_glider() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut automaton = SparseAutomaton::new(glider, Neighbourhood::Moore);
+        for _ in 0..400 {
+            automaton.step(life_sparse);
+        }
+        let moved = glider.map(|(x, y)| (x + 100, y + 100));
+        assert_eq!(automaton.sorted_live(), moved);
+
+        // stabilisation
+        let mut block =
+            SparseAutomaton::new([(0, 0), (0, 1), (1, 0), (1, 1)], Neighbourhood::Moore);
+        let cycle = find_cycle(
+            &mut block,
+            |a| a.step(life_sparse),
+            SparseAutomaton::sorted_live,
+            10,
+        );
+        assert_eq!(
+            cycle,
+            Some(Cycle {
+                start: 0,
+                period: 1
+            })
+        );
+    }
+
+    struct Counter;
+
+    impl CascadeRule<u8> for Counter {
+        fn charge(&self, cell: &mut u8) -> bool {
+            *cell += 1;
+            *cell > 2
+        }
+
+        fn propagate(&self, cell: &mut u8) -> bool {
+            *cell += 1;
+            *cell > 2
+        }
+
+        fn settle(&self, cell: &mut u8) {
+            *cell = 0;
+        }
+    }
+
+    #[test]
+    fn cascade() {
+        let grid = Grid::from_rows([[2, 1, 0, 0, 1]]);
+        let mut automaton = GridAutomaton::new(grid, Neighbourhood::VonNeumann, Boundary::Bounded);
+        assert_eq!(automaton.step_cascade(&Counter), [(0, 0), (1, 0)]);
+        assert_eq!(automaton.grid(), &Grid::from_rows([[0, 0, 2, 1, 2]]));
+
+        let grid = Grid::from_rows([[2, 1, 0, 0, 1]]);
+        let mut automaton = GridAutomaton::new(grid, Neighbourhood::VonNeumann, Boundary::Toroidal);
+        assert_eq!(automaton.step_cascade(&Counter), [(0, 0), (1, 0), (4, 0)]);
+        assert_eq!(automaton.grid(), &Grid::from_rows([[0, 0, 2, 2, 0]]));
+    }
+}
@@ -10,6 +10,7 @@ pub mod libs {
     pub use regex;
     pub use bitvec;
     pub use hex;
+    pub use num_bigint;
 
     pub use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 
@@ -25,6 +26,10 @@ macro_rules! debug_do {
 pub mod parse;
 pub mod structs;
 pub mod grids;
+pub mod automaton;
+pub mod linalg;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod rng;
 
 pub fn parse_lines<T: FromStr<Err = E>, E>(s: impl AsRef<str>) -> Result<Vec<T>, E> {
     s.as_ref().lines().map(str::parse).collect()
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use num_bigint::BigUint;
+
+/// Matrix entries: anything with addition, multiplication and their identities.
+pub trait Element: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn from_u64(v: u64) -> Self;
+    fn plus(&self, rhs: &Self) -> Self;
+    fn times(&self, rhs: &Self) -> Self;
+}
+
+macro_rules! impl_element {
+    ($($t:ty),*) => {$(
+        impl Element for $t {
+            fn zero() -> Self { 0 }
+            fn one() -> Self { 1 }
+            fn from_u64(v: u64) -> Self { v.try_into().expect("value out of range") }
+            fn plus(&self, rhs: &Self) -> Self { self.checked_add(*rhs).expect("overflow") }
+            fn times(&self, rhs: &Self) -> Self { self.checked_mul(*rhs).expect("overflow") }
+        }
+    )*};
+}
+
+impl_element!(u32, u64, u128, usize);
+
+impl Element for BigUint {
+    fn zero() -> Self {
+        BigUint::default()
+    }
+
+    fn one() -> Self {
+        BigUint::from(1u8)
+    }
+
+    fn from_u64(v: u64) -> Self {
+        BigUint::from(v)
+    }
+
+    fn plus(&self, rhs: &Self) -> Self {
+        self + rhs
+    }
+
+    fn times(&self, rhs: &Self) -> Self {
+        self * rhs
+    }
+}
+
+/// Integer modulo `M`, for results too large for any fixed-width type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modular<const M: u64>(pub u64);
+
+impl<const M: u64> Element for Modular<M> {
+    fn zero() -> Self {
+        Modular(0)
+    }
+
+    fn one() -> Self {
+        Modular(1 % M)
+    }
+
+    fn from_u64(v: u64) -> Self {
+        Modular(v % M)
+    }
+
+    fn plus(&self, rhs: &Self) -> Self {
+        Modular(((self.0 as u128 + rhs.0 as u128) % M as u128) as u64)
+    }
+
+    fn times(&self, rhs: &Self) -> Self {
+        Modular(((self.0 as u128 * rhs.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> fmt::Display for Modular<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Square matrix, stored row-major. Indexed with `(row, column)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Matrix<T> {
+    size: usize,
+    values: Vec<T>,
+}
+
+impl<T: Element> Matrix<T> {
+    pub fn zero(size: usize) -> Self {
+        Matrix {
+            size,
+            values: vec![T::zero(); size * size],
+        }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        Self::from_fn(size, |row, col| if row == col { T::one() } else { T::zero() })
+    }
+
+    pub fn from_fn(size: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let values = (0..size * size).map(|i| f(i / size, i % size)).collect();
+        Matrix { size, values }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn mul(&self, rhs: &Self) -> Self {
+        assert_eq!(self.size, rhs.size, "matrix sizes differ");
+        let mut result = Self::zero(self.size);
+        for row in 0..self.size {
+            for k in 0..self.size {
+                let lhs = &self[(row, k)];
+                for col in 0..self.size {
+                    result[(row, col)] = result[(row, col)].plus(&lhs.times(&rhs[(k, col)]));
+                }
+            }
+        }
+        result
+    }
+
+    /// `self` raised to `exp` by repeated squaring, in `O(size³ log exp)`.
+    pub fn pow(&self, mut exp: u64) -> Self {
+        let mut result = Self::identity(self.size);
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+
+    /// Product with the column vector `v`.
+    pub fn apply(&self, v: &[T]) -> Vec<T> {
+        assert_eq!(self.size, v.len(), "vector size differs");
+        (0..self.size)
+            .map(|row| {
+                (0..self.size).fold(T::zero(), |acc, col| {
+                    acc.plus(&self[(row, col)].times(&v[col]))
+                })
+            })
+            .collect()
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        &self.values[row * self.size + col]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        &mut self.values[row * self.size + col]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fibonacci<T: Element>(n: u64) -> T {
+        let step = Matrix::from_fn(2, |row, col| T::from_u64((row + col < 2) as u64));
+        step.pow(n).apply(&[T::zero(), T::one()])[0].clone()
+    }
+
+    #[test]
+    fn fibonacci_numbers() {
+        assert_eq!(fibonacci::<u64>(0), 0);
+        assert_eq!(fibonacci::<u64>(1), 1);
+        assert_eq!(fibonacci::<u64>(10), 55);
+        assert_eq!(fibonacci::<u64>(90), 2880067194370816120);
+        assert_eq!(fibonacci::<u128>(150), 9969216677189303386214405760200);
+        assert_eq!(
+            fibonacci::<BigUint>(150),
+            BigUint::from(9969216677189303386214405760200u128)
+        );
+
+        let (mut a, mut b) = (BigUint::zero(), BigUint::one());
+        for _ in 0..1000 {
+            let next = a.plus(&b);
+            a = std::mem::replace(&mut b, next);
+        }
+        assert_eq!(fibonacci::<BigUint>(1000), a);
+    }
+
+    #[test]
+    fn modulus() {
+        const M: u64 = 1_000_000_007;
+        assert_eq!(
+            fibonacci::<Modular<M>>(150),
+            Modular((9969216677189303386214405760200u128 % M as u128) as u64)
+        );
+        // Pisano period of 10 is 60
+        assert_eq!(fibonacci::<Modular<10>>(1_000_000_000_000_000_007).0, 3);
+        assert_eq!(Modular::<1>::one(), Modular(0));
+    }
+
+    #[test]
+    fn pow_is_repeated_mul() {
+        let m = Matrix::from_fn(3, |row, col| (row * 3 + col) as u128 % 4);
+        let mut expected = Matrix::identity(3);
+        for exp in 0..20 {
+            assert_eq!(m.pow(exp), expected);
+            expected = expected.mul(&m);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+/// Small deterministic PRNG for randomised tests. The seed must not be zero.
+#[derive(Debug, Clone)]
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Number in `0..n`, very slightly biased towards small values.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let (mut a, mut b) = (XorShift(0x5eed), XorShift(0x5eed));
+        for _ in 0..100 {
+            let n = a.below(7);
+            assert!(n < 7);
+            assert_eq!(n, b.below(7));
+        }
+    }
+}